@@ -1,35 +1,300 @@
-use clap::Parser;
+use std::{path::{self, Path, PathBuf}, env, ffi::OsString, fs};
 
-use crate::parsing::interpreter::get_interpreter;
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::parsing::{interpreter::{get_stages, get_reburn_args, Interpreters, ScriptConfig, Stages}, selector::MatchOptions};
+
+/// Milliseconds between restarts when not given
+const DEFAULT_DELAY: u64 = 50;
+
+/// Where relative paths start from in script mode
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Base {
+	/// The directory of the script
+	Script,
+	/// The directory reburn was run from
+	Cwd
+}
+
+/// Shells that can run the command
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Shell {
+	Sh,
+	Bash,
+	Zsh
+}
+
+impl Shell {
+	fn program(self) -> &'static str {
+		match self {
+			Shell::Sh => "sh",
+			Shell::Bash => "bash",
+			Shell::Zsh => "zsh"
+		}
+	}
+}
+
+fn env_var(s: &str) -> Result<(String, String), String> {
+	match s.split_once('=') {
+		Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+		_ => Err("expected NAME=value".to_string())
+	}
+}
 
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct Cli {
-	#[arg(help = "Changes to watch")]
-	pub watch: String,
+	#[command(subcommand)]
+	pub subcommand: Option<Command>,
+
+	#[arg(help = "Changes to watch, guessed from the project files if not given")]
+	pub watch: Option<String>,
 
 	#[arg(help = "Script to run")]
 	pub script: Option<String>,
 
-	#[arg(last = true, help = "Command to run")]
-	pub cmd: Option<Vec<String>>
+	#[arg(last = true, help = "Command to run, or arguments for the script")]
+	pub cmd: Option<Vec<String>>,
+
+	#[arg(short = 'c', long = "command", conflicts_with = "cmd", help = "Command to run through the shell, as one string")]
+	pub shell_command: Option<String>,
+
+	#[arg(
+		long, value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "sh",
+		help = "Run the command through a shell, so pipes, && and redirects work [default: sh]"
+	)]
+	pub shell: Option<Shell>,
+
+	#[arg(long, help = "Changes that never restart the command")]
+	pub ignore: Option<String>,
+
+	#[arg(long, help = "Milliseconds to wait after a restart before the next one [default: 50]")]
+	pub delay: Option<u64>,
+
+	#[arg(long, value_enum, default_value = "script", help = "Where relative selectors start from in script mode")]
+	pub watch_from: Base,
+
+	#[arg(long, value_enum, default_value = "cwd", help = "Working directory of the script")]
+	pub run_from: Base,
+
+	#[arg(short, long, value_parser = env_var, help = "Environment variable for the command, as NAME=value")]
+	pub env: Vec<(String, String)>,
+
+	#[arg(long, help = "Also watch the local files a Python or JavaScript script imports")]
+	pub follow_imports: bool,
+
+	#[arg(short, long, global = true, help = "Match names ignoring case")]
+	pub ignore_case: bool,
+
+	#[arg(long, global = true, help = "Let * and ** match names starting with a dot")]
+	pub hidden: bool
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+	/// Show what a selector will watch and exit
+	Explain {
+		#[arg(help = "Selector to explain")]
+		selector: String,
+
+		#[arg(long, help = "Changes that are never watched")]
+		ignore: Option<String>,
+
+		#[arg(long, help = "Print the explanation as JSON")]
+		json: bool
+	},
+	/// Test which paths a selector matches without reading the filesystem
+	Match {
+		#[arg(help = "Selector to test")]
+		selector: String,
+
+		#[arg(required = true, help = "Paths to test")]
+		paths: Vec<PathBuf>
+	}
 }
 
 impl Cli {
 	pub fn new() -> Self {
-		Self::parse()
+		Self::from_shebang(&env::args_os().collect::<Vec<_>>()).unwrap_or_else(Self::parse)
 	}
 
-	pub fn command(&self) -> Result<Vec<String>, String> {
+	/// A script starting with `#!reburn <args>` is run by the kernel as `reburn "<args>" <script> <script args>`,
+	/// the arguments are read again from the script and the ones after it are passed to the script.
+	/// Only the first regular file before `--` can be the script, nothing else is opened
+	fn from_shebang(args: &[OsString]) -> Option<Self> {
+		let subcommands: Vec<_> = <Self as clap::CommandFactory>::command().get_subcommands().map(|c| c.get_name().to_string()).collect();
+		if args.get(1).and_then(|a| a.to_str()).is_some_and(|a| subcommands.iter().any(|s| s == a)) {
+			return None
+		}
+		let at = (1..args.len()).take_while(|at| args[*at] != "--")
+			.find(|at| fs::metadata(&args[*at]).is_ok_and(|m| m.is_file()))?;
+		Self::from_shebang_at(args, at)
+	}
+
+	fn from_shebang_at(args: &[OsString], at: usize) -> Option<Self> {
+		let (line_args, raw) = get_reburn_args(&args[at]).ok()??;
+		let given: Vec<&str> = args[1..at].iter().map(|a| a.to_str()).collect::<Option<_>>()?;
+		let mut words = line_args;
+		// some systems split the line on spaces instead of passing it whole
+		// through `env -S` the words arrive already split, and running `reburn <script>` gives none
+		if !given.is_empty() && given != [raw.as_str()] && given != raw.split_whitespace().collect::<Vec<_>>() && given != words {
+			// flags given in the terminal, like `reburn --delay 10 script.py`, win over the ones of the line,
+			// with a selector it is an ordinary command line
+			let flags = Self::try_parse_from(args[..1].iter().cloned().chain(given.iter().map(OsString::from))).ok()?;
+			if flags.watch.is_some() || flags.script.is_some() {
+				return None
+			}
+			words.extend(given.iter().map(|g| g.to_string()));
+		}
+		let script = args[at].clone().into_string().ok()?;
+		let script_args: Vec<String> = args[at + 1..].iter().map(|a| a.clone().into_string().ok()).collect::<Option<_>>()?;
+		let matches = <Self as clap::CommandFactory>::command().args_override_self(true)
+			.try_get_matches_from(args[..1].iter().cloned().chain(words.into_iter().map(OsString::from)))
+			.unwrap_or_else(|e| e.exit());
+		let mut cli = <Self as clap::FromArgMatches>::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+		cli.script = Some(script);
+		if !script_args.is_empty() {
+			cli.cmd.get_or_insert_with(Vec::new).extend(script_args);
+		}
+		Some(cli)
+	}
+
+	/// The absolute directory for `base`, `None` for the working directory
+	pub fn dir_of(&self, base: Base) -> Option<PathBuf> {
+		match (base, &self.script) {
+			(Base::Script, Some(script)) => {
+				let parent = Path::new(script).parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+				path::absolute(parent).ok()
+			},
+			_ => None
+		}
+	}
+
+	pub fn delay(&self) -> u64 {
+		self.delay.unwrap_or(DEFAULT_DELAY)
+	}
+
+	/// Fills what was not given in the command line with the settings of the script
+	pub fn merge(&mut self, config: ScriptConfig) {
+		self.watch = self.watch.take().or(config.watch);
+		self.ignore = self.ignore.take().or(config.ignore);
+		self.delay = self.delay.or(config.delay);
+		// later values win, so the command line goes last
+		self.env.splice(0..0, config.env);
+	}
+
+	pub fn match_options(&self) -> MatchOptions {
+		MatchOptions { ignore_case: self.ignore_case, hidden: self.hidden }
+	}
+
+	/// The command line given to the shell in shell mode, the words of the command are joined like `sh -c "$*"` does
+	fn shell_line(&self) -> Option<String> {
+		self.shell_command.clone().or_else(|| self.cmd.as_ref().filter(|_| self.shell.is_some()).map(|v| v.join(" ")))
+	}
+
+	pub fn command(&self, interpreters: &Interpreters) -> Result<Stages, String> {
+		if let Some(line) = self.shell_line() {
+			if self.script.is_some() {
+				return Err("A shell runs a command, not a script".to_string())
+			}
+			let shell = self.shell.unwrap_or(Shell::Sh).program().to_string();
+			return Ok(Stages { build: Vec::new(), run: vec![shell, "-c".to_string(), line], shebang: false })
+		}
 		match (&self.cmd, &self.script) {
-			(Some(_), Some(_)) | (None, None) => Err("Expected either a script or a command".to_string()),
-			(Some(v), None) => Ok(v.clone()),
-			(None, Some(script)) => match get_interpreter(script) {
-				Ok(mut v) => {
-					v.push(script.clone());
-					Ok(v)
-				},
-				Err(e) => Err(e.to_string()),
+			(None, None) => Err("Expected either a script or a command".to_string()),
+			(Some(v), None) => Ok(Stages { build: Vec::new(), run: v.clone(), shebang: false }),
+			(args, Some(script)) => {
+				let mut stages = get_stages(script, interpreters).map_err(|e| e.to_string())?;
+				stages.run.extend(args.iter().flatten().cloned());
+				Ok(stages)
 			}
 		}
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::testing::TempDir;
+	use super::*;
+
+	#[test]
+	fn test_from_shebang() {
+		let dir = TempDir::new("cli");
+		let script = dir.join("file.py");
+		let args = |v: &[&str]| v.iter().map(OsString::from).chain([script.clone().into_os_string()]).collect::<Vec<_>>();
+		fs::write(&script, "#!reburn --delay 200 \"{file.py,lib/**/*.py}\"\n#!python\n").unwrap();
+		let cli = Cli::from_shebang(&args(&["reburn", "--delay 200 \"{file.py,lib/**/*.py}\""])).unwrap();
+		assert_eq!((cli.delay(), cli.watch.as_deref()), (200, Some("{file.py,lib/**/*.py}")));
+		assert_eq!(cli.script.as_deref(), script.to_str());
+		assert!(Cli::from_shebang(&args(&["reburn", "--delay", "200", "\"{file.py,lib/**/*.py}\""])).is_some());
+		assert!(Cli::from_shebang(&args(&["reburn", "src/**"])).is_none());
+		let mut with_args = args(&["reburn", "--delay 200 \"{file.py,lib/**/*.py}\""]);
+		with_args.extend(["--port", "8000"].map(OsString::from));
+		let cli = Cli::from_shebang(&with_args).unwrap();
+		assert_eq!((cli.script.as_deref(), cli.cmd), (script.to_str(), Some(vec!["--port".to_string(), "8000".to_string()])));
+		let cli = Cli::parse_from([OsString::from("reburn"), OsString::from("*.py"), script.clone().into_os_string()]
+			.into_iter().chain(["--", "--port", "8000"].map(OsString::from)));
+		let stages = cli.command(&Interpreters::default()).unwrap();
+		assert_eq!(stages.run, ["python", script.to_str().unwrap(), "--port", "8000"]);
+		fs::write(&script, "#!reburn\n#!python\n").unwrap();
+		let cli = Cli::from_shebang(&args(&["reburn"])).unwrap();
+		assert_eq!((cli.delay(), cli.watch.as_deref(), cli.script.as_deref()), (50, None, script.to_str()));
+		// nothing after `--`, in a subcommand or that is not a regular file is opened
+		assert!(Cli::from_shebang(&args(&["reburn", "*.py", "--"])).is_none());
+		assert!(Cli::from_shebang(&args(&["reburn", "explain"])).is_none());
+		// flags from the terminal go on top of the reburn line
+		fs::write(&script, "#!reburn --delay 200 -e A=1 \"*.py\"\n#!python\n").unwrap();
+		let cli = Cli::from_shebang(&args(&["reburn", "--delay", "10", "-i", "-e", "B=2"])).unwrap();
+		assert_eq!((cli.delay(), cli.watch.as_deref(), cli.ignore_case), (10, Some("*.py"), true));
+		assert_eq!(cli.env, [("A".to_string(), "1".to_string()), ("B".to_string(), "2".to_string())]);
+		assert!(Cli::from_shebang(&args(&["reburn", "--delay", "10", "src/**"])).is_none());
+		#[cfg(unix)]
+		{
+			use std::os::unix::ffi::OsStrExt;
+			let fifo = dir.join("fifo");
+			let path = std::ffi::CString::new(fifo.as_os_str().as_bytes()).unwrap();
+			assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o600) }, 0);
+			assert!(Cli::from_shebang(&[OsString::from("reburn"), OsString::from("x"), fifo.into_os_string()]).is_none());
+		}
+	}
+
+	#[test]
+	fn test_dir_of() {
+		let cli = Cli::parse_from(["reburn", "*.py", "../tools/gen.py"]);
+		let cwd = env::current_dir().unwrap();
+		assert_eq!(cli.dir_of(cli.watch_from), Some(cwd.join("../tools")));
+		assert_eq!(cli.dir_of(cli.run_from), None);
+		let cli = Cli::parse_from(["reburn", "--watch-from", "cwd", "--run-from", "script", "*.py", "gen.py"]);
+		assert_eq!((cli.dir_of(cli.watch_from), cli.dir_of(cli.run_from)), (None, Some(cwd.join("."))));
+		let cli = Cli::parse_from(["reburn", "src/**", "--", "cargo", "run"]);
+		assert_eq!(cli.dir_of(Base::Script), None);
+	}
+
+	#[test]
+	fn test_merge() {
+		let mut cli = Cli::parse_from(["reburn", "--delay", "10", "-e", "A=1", "src/**", "file.py"]);
+		cli.merge(ScriptConfig {
+			watch: Some("lib/**".to_string()), ignore: Some("dist/**".to_string()), delay: Some(300),
+			env: vec![("A".to_string(), "0".to_string()), ("B".to_string(), "2".to_string())]
+		});
+		assert_eq!((cli.watch.as_deref(), cli.ignore.as_deref(), cli.delay()), (Some("src/**"), Some("dist/**"), 10));
+		assert_eq!(cli.env, [("A", "0"), ("B", "2"), ("A", "1")].map(|(n, v)| (n.to_string(), v.to_string())));
+	}
+
+	#[test]
+	fn test_shell() {
+		let run = |args: &[&str]| Cli::try_parse_from(["reburn"].iter().chain(args)).map_err(|e| e.kind())
+			.map(|cli| cli.command(&Interpreters::default()).map(|s| s.run));
+		assert_eq!(run(&["src/**", "-c", "make && ./app | tee log"]), Ok(Ok(vec![
+			"sh".to_string(), "-c".to_string(), "make && ./app | tee log".to_string()
+		])));
+		assert_eq!(run(&["--shell=bash", "src/**", "--", "make", "&&", "./app"]), Ok(Ok(vec![
+			"bash".to_string(), "-c".to_string(), "make && ./app".to_string()
+		])));
+		assert_eq!(run(&["--shell", "src/**", "--", "make"]).map(|r| r.map(|v| v[0].clone())), Ok(Ok("sh".to_string())));
+		assert_eq!(run(&["src/**", "--", "make", "&&", "./app"]).map(|r| r.map(|v| v.len())), Ok(Ok(3)));
+		assert_eq!(run(&["--shell", "*.py", "app.py", "--", "x"]), Ok(Err("A shell runs a command, not a script".to_string())));
+		assert_eq!(run(&["--shell=fish", "src/**", "--", "make"]), Err(clap::error::ErrorKind::InvalidValue));
+		assert_eq!(run(&["src/**", "-c", "make", "--", "make"]), Err(clap::error::ErrorKind::ArgumentConflict));
+	}
+}
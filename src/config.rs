@@ -0,0 +1,99 @@
+use std::{env, fs, io::ErrorKind, path::PathBuf};
+
+use crate::parsing::{alias::Aliases, interpreter::{Interpreters, split_words}};
+
+/// Settings read from the user config file
+///
+/// ```text
+/// [alias]
+/// web = {src,static}/**
+///
+/// [interpreter]
+/// py = python3.12 -u
+/// ```
+#[derive(Debug, Default)]
+pub struct Config {
+  pub aliases: Aliases,
+  pub interpreters: Interpreters
+}
+
+/// `$REBURN_CONFIG`, or `reburn/config` inside the user config directory
+fn config_path() -> Option<PathBuf> {
+  let var = |name| env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+  if let Some(path) = var("REBURN_CONFIG") {
+    return Some(path)
+  }
+  let dir = if cfg!(windows) {
+    var("APPDATA")
+  } else {
+    var("XDG_CONFIG_HOME").or_else(|| var("HOME").map(|home| home.join(".config")))
+  };
+  dir.map(|dir| dir.join("reburn").join("config"))
+}
+
+impl Config {
+  pub fn parse(source: &str) -> Result<Self, String> {
+    let mut config = Config::default();
+    let mut section = None;
+    for (n, line) in source.lines().enumerate() {
+      let fail = |message: &str| Err(format!("Config line {}: {}", n + 1, message));
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+        continue
+      }
+      if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+        section = Some(name.trim().to_string());
+        continue
+      }
+      let Some((key, value)) = line.split_once('=') else {
+        return fail("expected `key = value`")
+      };
+      let (key, value) = (key.trim(), value.trim());
+      match section.as_deref() {
+        Some("alias") if Aliases::is_valid_name(key) => config.aliases.define(key, value),
+        Some("alias") => return fail("alias names use letters, digits, `_` and `-`"),
+        Some("interpreter") => match split_words(value) {
+          Ok(command) if !command.is_empty() => config.interpreters.define(key, command),
+          Ok(_) => return fail("expected a command"),
+          Err(e) => return fail(&e)
+        },
+        Some(other) => return fail(&format!("unknown section `{}`", other)),
+        None => return fail("expected a section like `[alias]`")
+      }
+    }
+    Ok(config)
+  }
+
+  /// Reads the config file, a missing file is an empty config
+  pub fn load() -> Result<Self, String> {
+    let Some(path) = config_path() else {
+      return Ok(Config::default())
+    };
+    match fs::read_to_string(&path) {
+      Ok(source) => Self::parse(&source).map_err(|e| format!("{} ({})", e, path.display())),
+      Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+      Err(e) => Err(format!("Can not read {}: {}", path.display(), e))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse() {
+    let config = Config::parse("# presets\n[alias]\n web = {src,static}/** \nrust=src/**\n").unwrap();
+    assert_eq!(config.aliases.get("web"), Some("{src,static}/**"));
+    assert_eq!(config.aliases.get("rust"), Some("src/**"));
+    assert_eq!(config.aliases.get("go"), Some("**/*.go,go.mod,go.sum"));
+    let config = Config::parse("[interpreter]\n.py = python3.12 -u\njs = 'my node'\n").unwrap();
+    assert_eq!(config.interpreters.of("a.py"), Some(vec!["python3.12".to_string(), "-u".to_string()]));
+    assert_eq!(config.interpreters.of("a.js"), Some(vec!["my node".to_string()]));
+    assert_eq!(Config::parse("[interpreter]\npy =").unwrap_err(), "Config line 2: expected a command");
+    assert_eq!(Config::parse("web = a").unwrap_err(), "Config line 1: expected a section like `[alias]`");
+    assert_eq!(Config::parse("[alias]\n\nweb").unwrap_err(), "Config line 3: expected `key = value`");
+    assert_eq!(Config::parse("[alias]\n@web = a").unwrap_err(), "Config line 2: alias names use letters, digits, `_` and `-`");
+    assert_eq!(Config::parse("[other]\na = b").unwrap_err(), "Config line 2: unknown section `other`");
+  }
+}
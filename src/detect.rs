@@ -0,0 +1,56 @@
+use std::path::Path;
+
+/// Selectors used for a kind of project when none is given
+#[derive(Debug, PartialEq)]
+pub struct Project {
+  pub name: &'static str,
+  /// Any of these files marks the project
+  markers: &'static [&'static str],
+  pub watch: &'static str,
+  pub ignore: &'static str
+}
+
+/// Checked in order, the first one with a marker in the directory wins
+const PROJECTS: [Project; 4] = [
+  Project {
+    name: "Rust", markers: &["Cargo.toml"],
+    watch: "src/**,Cargo.toml,Cargo.lock,build.rs", ignore: "target/**"
+  },
+  Project {
+    name: "Go", markers: &["go.mod"],
+    watch: "@go", ignore: "vendor/**"
+  },
+  Project {
+    name: "Python", markers: &["pyproject.toml", "setup.py", "setup.cfg", "requirements.txt"],
+    watch: "@python", ignore: "{venv,build,dist}/**,**/__pycache__/**"
+  },
+  Project {
+    name: "Node", markers: &["package.json"],
+    watch: "@node", ignore: "{dist,build,coverage}/**"
+  },
+];
+
+/// The kind of project in the directory, from the files it has
+pub fn detect(dir: &Path) -> Option<&'static Project> {
+  PROJECTS.iter().find(|p| p.markers.iter().any(|m| dir.join(m).is_file()))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+  use crate::testing::TempDir;
+  use super::*;
+
+  #[test]
+  fn test_detect() {
+    let root = TempDir::new("detect");
+    fs::create_dir_all(root.join("go.mod")).unwrap();
+    assert_eq!(detect(&root), None);
+    fs::write(root.join("package.json"), "{}").unwrap();
+    assert_eq!(detect(&root).map(|p| p.name), Some("Node"));
+    fs::write(root.join("requirements.txt"), "").unwrap();
+    assert_eq!(detect(&root).map(|p| p.name), Some("Python"));
+    fs::write(root.join("Cargo.toml"), "").unwrap();
+    assert_eq!(detect(&root).map(|p| p.name), Some("Rust"));
+  }
+}
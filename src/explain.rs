@@ -0,0 +1,193 @@
+use std::{path::{Path, PathBuf}, sync::Arc};
+
+use notify::RecursiveMode;
+
+use crate::parsing::{selector::{Selector, MatchOptions, parse_selector_with}, alias::Aliases};
+use crate::walk;
+
+/// Everything reburn derives from a selector before watching
+#[derive(Debug)]
+pub struct Explanation {
+  pub selector: Selector,
+  pub routes: Vec<String>,
+  pub watch: Vec<(PathBuf, RecursiveMode)>
+}
+
+fn selector_name(selector: &Selector) -> String {
+  use Selector::*;
+
+  match selector {
+    Option(_) => "Option".to_string(),
+    Route(..) => "Route".to_string(),
+    Concat(..) => "Concat".to_string(),
+    WildCard => "WildCard".to_string(),
+    WildCardDepth => "WildCardDepth".to_string(),
+    WildCardDepthBounded(min, max) => format!(
+      "WildCardDepth {{{},{}}}", min, max.map(|m| m.to_string()).unwrap_or_default()
+    ),
+    Directory => "Directory".to_string(),
+    Not(..) => "Not".to_string(),
+    Word(w) => format!("Word {:?}", w),
+    Root => "Root".to_string(),
+    Home => "Home".to_string(),
+    Flags(options, _) => format!("Flags {}", options),
+    Alias(name, ..) => format!("Alias @{}", name),
+  }
+}
+
+fn selector_children(selector: &Selector) -> Vec<&Selector> {
+  use Selector::*;
+
+  match selector {
+    Option(v) | Route(v, _) | Concat(v, _) => v.iter().collect(),
+    Not(s, _) | Flags(_, s) | Alias(_, s, _) => vec![s.as_ref()],
+    _ => Vec::new()
+  }
+}
+
+fn selector_tree(selector: &Selector, depth: usize, out: &mut Vec<String>) {
+  out.push(format!("{}{}", "  ".repeat(depth), selector_name(selector)));
+  selector_children(selector).into_iter().for_each(|s| selector_tree(s, depth + 1, out));
+}
+
+fn json_string(s: &str) -> String {
+  let mut out = String::from("\"");
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+      c => out.push(c)
+    }
+  }
+  out.push('"');
+  out
+}
+
+fn json_list<T, F: Fn(&T) -> String>(items: &[T], f: F) -> String {
+  format!("[{}]", items.iter().map(f).collect::<Vec<_>>().join(","))
+}
+
+fn selector_json(selector: &Selector) -> String {
+  use Selector::*;
+
+  let kind = match selector {
+    Word(_) => "Word".to_string(),
+    Flags(..) => "Flags".to_string(),
+    Alias(..) => "Alias".to_string(),
+    WildCardDepthBounded(..) => "WildCardDepthBounded".to_string(),
+    s => selector_name(s)
+  };
+  match selector {
+    Word(w) => format!("{{\"kind\":{},\"value\":{}}}", json_string(&kind), json_string(w)),
+    Not(s, _) => format!("{{\"kind\":{},\"item\":{}}}", json_string(&kind), selector_json(s)),
+    WildCardDepthBounded(min, max) => format!(
+      "{{\"kind\":{},\"min\":{},\"max\":{}}}", json_string(&kind), min, max.map(|m| m.to_string()).unwrap_or("null".to_string())
+    ),
+    Flags(options, s) => format!(
+      "{{\"kind\":{},\"flags\":{},\"item\":{}}}", json_string(&kind), json_string(&options.to_string()), selector_json(s)
+    ),
+    Alias(name, s, _) => format!(
+      "{{\"kind\":{},\"name\":{},\"item\":{}}}", json_string(&kind), json_string(name), selector_json(s)
+    ),
+    Option(v) | Route(v, _) | Concat(v, _) =>
+      format!("{{\"kind\":{},\"items\":{}}}", json_string(&kind), json_list(v, selector_json)),
+    _ => format!("{{\"kind\":{}}}", json_string(&kind))
+  }
+}
+
+impl Explanation {
+  /// What `ignore` matches is left out of the watch list, relative routes start from `relative_to` like when running
+  pub fn of(pattern: &str, ignore: Option<&str>, relative_to: &Path, options: MatchOptions, aliases: &Aliases)
+  -> Result<Self, String> {
+    let selector = parse_selector_with(pattern, aliases).map_err(|e| e.render())?;
+    let routes = walk::expand(&selector)?;
+    let ignore = match ignore {
+      Some(ignore) => Some(Arc::new(walk::Matcher::new(&parse_selector_with(ignore, aliases).map_err(|e| e.render())?, options)?)),
+      None => None
+    };
+    let mut watch: Vec<_> = walk::matches(selector.clone(), options, relative_to, ignore)?.collect();
+    watch.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(Explanation { selector, routes, watch })
+  }
+
+  fn recursive_count(&self) -> usize {
+    self.watch.iter().filter(|(_, m)| *m == RecursiveMode::Recursive).count()
+  }
+
+  pub fn to_text(&self) -> String {
+    let mut out = vec!["Selector".to_string()];
+    selector_tree(&self.selector, 1, &mut out);
+    out.push(format!("Routes ({})", self.routes.len()));
+    out.extend(self.routes.iter().map(|r| format!("  {}", r)));
+    let recursive = self.recursive_count();
+    out.push(format!(
+      "Watch list ({}: {} recursive, {} non-recursive)",
+      self.watch.len(), recursive, self.watch.len() - recursive
+    ));
+    out.extend(self.watch.iter().map(|(path, mode)| format!("  {:<14} {}", match mode {
+      RecursiveMode::Recursive => "recursive",
+      RecursiveMode::NonRecursive => "non-recursive",
+    }, path.display())));
+    out.join("\n")
+  }
+
+  pub fn to_json(&self) -> String {
+    let recursive = self.recursive_count();
+    format!(
+      "{{\"selector\":{},\"routes\":{},\"watch\":{},\"counts\":{{\"routes\":{},\"watch\":{},\"recursive\":{},\"non_recursive\":{}}}}}",
+      selector_json(&self.selector),
+      json_list(&self.routes, |r| json_string(r)),
+      json_list(&self.watch, |(path, mode)| format!(
+        "{{\"path\":{},\"recursive\":{}}}",
+        json_string(&path.to_string_lossy()), *mode == RecursiveMode::Recursive
+      )),
+      self.routes.len(), self.watch.len(), recursive, self.watch.len() - recursive
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use crate::{parsing::selector::parse_selector, testing::TempDir};
+
+  #[test]
+  fn test_explain() {
+    let explanation = Explanation {
+      selector: parse_selector("{a,\"b\"}/**").unwrap(),
+      routes: vec!["a/**".to_string(), "\"b\"/**".to_string()],
+      watch: vec![(PathBuf::from("./a"), RecursiveMode::Recursive)]
+    };
+    assert_eq!(explanation.to_text(), [
+      "Selector",
+      "  Route",
+      "    Option",
+      "      Word \"a\"",
+      "      Word \"\\\"b\\\"\"",
+      "    WildCardDepth",
+      "Routes (2)",
+      "  a/**",
+      "  \"b\"/**",
+      "Watch list (1: 1 recursive, 0 non-recursive)",
+      "  recursive      ./a",
+    ].join("\n"));
+    assert_eq!(explanation.to_json(), concat!(
+      r#"{"selector":{"kind":"Route","items":[{"kind":"Option","items":[{"kind":"Word","value":"a"},{"kind":"Word","value":"\"b\""}]},{"kind":"WildCardDepth"}]},"#,
+      r#""routes":["a/**","\"b\"/**"],"watch":[{"path":"./a","recursive":true}],"#,
+      r#""counts":{"routes":2,"watch":1,"recursive":1,"non_recursive":0}}"#
+    ));
+  }
+  #[test]
+  fn test_explain_from() {
+    let root = TempDir::new("explain");
+    fs::create_dir_all(root.join("lib/src")).unwrap();
+    fs::create_dir_all(root.join("app")).unwrap();
+    fs::write(root.join("lib/src/a.rs"), "").unwrap();
+    let explanation = Explanation::of("../lib/src/*", None, &root.join("app"), MatchOptions::default(), &Aliases::default()).unwrap();
+    assert_eq!(explanation.watch, [(root.join("lib/src/a.rs"), RecursiveMode::NonRecursive)]);
+  }
+}
@@ -0,0 +1,178 @@
+use std::{path::{self, Component, Path, PathBuf}, fs, collections::HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Language {
+  Python,
+  JavaScript
+}
+
+/// Extensions tried for a JavaScript import without one
+const JS_EXTENSIONS: [&str; 7] = ["js", "mjs", "cjs", "ts", "jsx", "tsx", "json"];
+
+impl Language {
+  /// From the extension of the script, or else the name of its interpreter
+  fn of(script: &Path, interpreter: &[String]) -> Option<Self> {
+    let extension = script.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    let program = interpreter.iter().map(|p| Path::new(p).file_name().and_then(|n| n.to_str()).unwrap_or_default())
+      .find(|p| *p != "env").unwrap_or_default();
+    match (extension, program) {
+      ("py", _) => Some(Self::Python),
+      (e, _) if JS_EXTENSIONS.contains(&e) => Some(Self::JavaScript),
+      ("", p) if p.starts_with("python") => Some(Self::Python),
+      ("", "node" | "deno" | "bun") => Some(Self::JavaScript),
+      _ => None
+    }
+  }
+}
+
+/// Modules of `import a.b` and `from .a import b` lines, relative ones keep their dots.
+/// For `from a import b` both `a` and `a.b` are given, `b` may be a module
+fn python_imports(source: &str) -> Vec<String> {
+  let mut modules = Vec::new();
+  for line in source.lines().map(str::trim) {
+    let line = line.split('#').next().unwrap_or_default();
+    if let Some(names) = line.strip_prefix("import ") {
+      modules.extend(names.split(',').filter_map(|n| n.split_whitespace().next()).map(String::from));
+    } else if let Some((module, names)) = line.strip_prefix("from ").and_then(|l| l.split_once(" import ")) {
+      let module = module.trim();
+      let separator = if module.ends_with('.') { "" } else { "." };
+      let names = names.trim().trim_start_matches('(').trim_end_matches(')');
+      modules.push(module.to_string());
+      modules.extend(names.split(',').filter_map(|n| n.split_whitespace().next())
+        .filter(|n| *n != "*").map(|n| format!("{}{}{}", module, separator, n)));
+    }
+  }
+  modules
+}
+
+/// Local specifiers of `import ... from`, `import '...'`, `export ... from`, `require()` and `import()`
+fn javascript_imports(source: &str) -> Vec<String> {
+  let mut specifiers = Vec::new();
+  for keyword in ["from", "import", "require(", "import("] {
+    for (at, _) in source.match_indices(keyword) {
+      // the keyword must not be the end of another name
+      if source[..at].chars().next_back().is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.') {
+        continue
+      }
+      let rest = source[at + keyword.len()..].trim_start();
+      let Some(quote) = rest.chars().next().filter(|c| matches!(c, '\'' | '"' | '`')) else {
+        continue
+      };
+      if let Some((specifier, _)) = rest[1..].split_once(quote) {
+        if specifier.starts_with("./") || specifier.starts_with("../") {
+          specifiers.push(specifier.to_string());
+        }
+      }
+    }
+  }
+  specifiers
+}
+
+/// Files a Python module can be. Like `sys.path[0]`, absolute imports start from the directory of the script,
+/// relative ones from the directory of the importing file
+fn python_candidates(script_dir: &Path, dir: &Path, module: &str) -> Vec<PathBuf> {
+  let dots = module.chars().take_while(|c| *c == '.').count();
+  let mut base = if dots == 0 { script_dir } else { dir }.to_path_buf();
+  // `.` is the same package, each extra dot goes one level up
+  for _ in 1..dots {
+    base.pop();
+  }
+  let path = module[dots..].split('.').filter(|p| !p.is_empty()).fold(base, |path, part| path.join(part));
+  vec![path.with_extension("py"), path.join("__init__.py")]
+}
+
+/// Removes `.` and `..` without touching the filesystem, so a file found twice has one path
+fn normalize(path: &Path) -> PathBuf {
+  path.components().fold(PathBuf::new(), |mut path, component| {
+    match component {
+      Component::CurDir => (),
+      Component::ParentDir => { path.pop(); },
+      other => path.push(other)
+    }
+    path
+  })
+}
+
+fn javascript_candidates(dir: &Path, specifier: &str) -> Vec<PathBuf> {
+  let path = normalize(&dir.join(specifier));
+  let mut candidates = vec![path.clone()];
+  candidates.extend(JS_EXTENSIONS.iter().map(|e| PathBuf::from(format!("{}.{}", path.display(), e))));
+  candidates.extend(JS_EXTENSIONS.iter().map(|e| path.join("index").with_extension(e)));
+  candidates
+}
+
+/// The local files a script imports, and the files they import, without the script
+pub fn follow(script: &Path, interpreter: &[String]) -> Vec<PathBuf> {
+  let Some(language) = Language::of(script, interpreter) else {
+    return Vec::new()
+  };
+  let Ok(script) = path::absolute(script).map(|s| normalize(&s)) else {
+    return Vec::new()
+  };
+  let script_dir = script.parent().map(Path::to_path_buf).unwrap_or_default();
+  let mut found = HashSet::from([script.clone()]);
+  let mut pending = vec![script.clone()];
+  while let Some(file) = pending.pop() {
+    let (Ok(source), Some(dir)) = (fs::read_to_string(&file), file.parent()) else {
+      continue
+    };
+    let candidates: Vec<_> = match language {
+      Language::Python => python_imports(&source).iter().map(|m| python_candidates(&script_dir, dir, m)).collect(),
+      Language::JavaScript => javascript_imports(&source).iter().map(|s| javascript_candidates(dir, s)).collect()
+    };
+    for path in candidates.into_iter().filter_map(|c| c.into_iter().find(|p| p.is_file())) {
+      if found.insert(path.clone()) {
+        pending.push(path);
+      }
+    }
+  }
+  found.remove(&script);
+  let mut found: Vec<_> = found.into_iter().collect();
+  found.sort();
+  found
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::testing::TempDir;
+  use super::*;
+
+  #[test]
+  fn test_python_imports() {
+    let source = "import os, lib.util as u\nfrom . import sibling  # local\nfrom ..pkg.mod import (a, b)\nfrom x import *\n  import json";
+    assert_eq!(python_imports(source), [
+      "os", "lib.util", ".", ".sibling", "..pkg.mod", "..pkg.mod.a", "..pkg.mod.b", "x", "json"
+    ]);
+  }
+
+  #[test]
+  fn test_javascript_imports() {
+    let source = "import a from './a.js';\nimport \"../b\";\nexport { c } from './c'\nconst d = require( './d' );\nconst e = await import(`./e.mjs`);\nimport fs from 'fs';\nthis.from('./no');";
+    assert_eq!(javascript_imports(source), ["./a.js", "./c", "../b", "./d", "./e.mjs"]);
+  }
+
+  #[test]
+  fn test_follow() {
+    let root = TempDir::new("imports");
+    fs::create_dir_all(root.join("lib/pkg")).unwrap();
+    fs::write(root.join("main.py"), "import os\nfrom lib import helper\nimport lib.pkg").unwrap();
+    fs::write(root.join("lib/__init__.py"), "").unwrap();
+    fs::write(root.join("lib/helper.py"), "from .pkg.deep import x").unwrap();
+    fs::write(root.join("lib/pkg/__init__.py"), "").unwrap();
+    fs::write(root.join("lib/pkg/deep.py"), "from .. import helper\nimport util").unwrap();
+    // `util` is found next to the script, not next to `deep.py`
+    fs::write(root.join("util.py"), "").unwrap();
+    fs::write(root.join("lib/pkg/util.py"), "").unwrap();
+    assert_eq!(follow(&root.join("main.py"), &[]), [
+      "lib/__init__.py", "lib/helper.py", "lib/pkg/__init__.py", "lib/pkg/deep.py", "util.py"
+    ].map(|p| root.join(p)));
+    fs::write(root.join("server"), "const a = require('./lib/a');").unwrap();
+    fs::write(root.join("lib/a.ts"), "import b from '../b'").unwrap();
+    fs::create_dir_all(root.join("b")).unwrap();
+    fs::write(root.join("b/index.js"), "").unwrap();
+    assert_eq!(follow(&root.join("server"), &["/usr/bin/env".to_string(), "node".to_string()]), [
+      "b/index.js", "lib/a.ts"
+    ].map(|p| root.join(p)));
+    assert!(follow(&root.join("server"), &[]).is_empty());
+  }
+}
@@ -3,33 +3,229 @@ mod cli;
 mod watcher;
 mod parsing;
 mod walk;
+mod explain;
+mod config;
+mod detect;
+mod imports;
+#[cfg(test)]
+mod testing;
 
-use std::time::SystemTime;
+use std::{time::{SystemTime, Instant, Duration}, process::ExitCode, path::{self, Path, PathBuf}, env, sync::{mpsc::RecvTimeoutError, Arc}, collections::HashSet};
 
-use parsing::selector::parse_selector;
+use notify::RecursiveMode;
+
+use parsing::{selector::{parse_selector_with, MatchOptions}, alias::Aliases, interpreter::get_directives};
+
+/// Wait before starting again a program that failed to start, doubled after each failure up to `MAX_RETRY`
+const FIRST_RETRY: Duration = Duration::from_millis(500);
+const MAX_RETRY: Duration = Duration::from_secs(30);
+
+/// The wait before the next try after a restart, none when it succeeded
+fn next_retry(succeeded: bool, previous: Option<Duration>) -> Option<Duration> {
+  match (succeeded, previous) {
+    (true, _) => None,
+    (false, None) => Some(FIRST_RETRY),
+    (false, Some(wait)) => Some((wait * 2).min(MAX_RETRY))
+  }
+}
 
 fn aux_to_str<E : ToString>(e: E) -> String {
   e.to_string()
 }
 
-const ELAPSE_TIME: u128 = 50;
+fn explain(selector: &str, ignore: Option<&str>, json: bool, options: MatchOptions, aliases: &Aliases) -> Result<(), String> {
+  let cwd = env::current_dir().map_err(aux_to_str)?;
+  let explanation = explain::Explanation::of(selector, ignore, &cwd, options, aliases)?;
+  println!("{}", if json { explanation.to_json() } else { explanation.to_text() });
+  Ok(())
+}
+
+fn test_paths(selector: &str, paths: &[PathBuf], options: MatchOptions, aliases: &Aliases) -> Result<(), String> {
+  let matcher = walk::Matcher::new(&parse_selector_with(selector, aliases).map_err(|e| e.render())?, options)?;
+  for path in paths {
+    match matcher.matching_route(path) {
+      Some(route) => println!("match     {}  ({})", path.display(), route),
+      None => println!("no-match  {}", path.display()),
+    }
+  }
+  Ok(())
+}
+
+/// Checks that `program` can be run, naming the script when its shebang gives a missing path
+fn check_program(program: &str, dir: Option<&Path>, shebang_of: Option<&str>) -> Result<(), String> {
+  ps::resolve(program, dir).map(drop).map_err(|e| match shebang_of {
+    Some(script) if Path::new(program).is_absolute() => format!("In the shebang of {}: {}", script, e),
+    _ => e
+  })
+}
+
+/// Runs the build steps in order, false when one fails and the program must not be restarted
+fn build(steps: &[Vec<String>], env: &[(String, String)], dir: Option<&Path>, script: Option<&str>) -> Result<bool, String> {
+  for step in steps {
+    check_program(&step[0], dir, script)?;
+    let status = ps::command(step, env, dir).status()
+      .map_err(|e| format!("Can not run {}: {}", step[0], e))?;
+    if !status.success() {
+      eprintln!("Build failed ({}), the program was not restarted", status);
+      return Ok(false)
+    }
+  }
+  Ok(true)
+}
+
+/// The selectors to watch and ignore, guessed from the project when no selector is given
+fn watch_and_ignore(parsed_args: &cli::Cli, cwd: &Path) -> Result<(String, Option<String>), String> {
+  if let Some(watch) = &parsed_args.watch {
+    return Ok((watch.clone(), parsed_args.ignore.clone()))
+  }
+  // a script run without a selector watches itself
+  if let Some(script) = &parsed_args.script {
+    let script = match parsed_args.watch_from {
+      cli::Base::Script => Path::new(script).file_name().and_then(|n| n.to_str()).unwrap_or(script),
+      cli::Base::Cwd => script
+    };
+    return Ok((script.to_string(), parsed_args.ignore.clone()))
+  }
+  let project = detect::detect(cwd).ok_or("No selector given and no known project found in the current directory")?;
+  let ignore = parsed_args.ignore.as_deref().unwrap_or(project.ignore);
+  eprintln!(
+    "{} project detected, watching \"{}\" and ignoring \"{}\", give a selector or --ignore to change it",
+    project.name, project.watch, ignore
+  );
+  Ok((project.watch.to_string(), Some(ignore.to_string())))
+}
 
-fn main() -> Result<(), String> {
-  let parsed_args = cli::Cli::new();
+fn run() -> Result<(), String> {
+  let mut parsed_args = cli::Cli::new();
+  let config = config::Config::load()?;
+  let aliases = &config.aliases;
+  match &parsed_args.subcommand {
+    Some(cli::Command::Explain { selector, ignore, json }) => return explain(
+      selector, ignore.as_deref(), *json, parsed_args.match_options(), aliases
+    ),
+    Some(cli::Command::Match { selector, paths }) => return test_paths(selector, paths, parsed_args.match_options(), aliases),
+    None => ()
+  }
 
-  let command = parsed_args.command()?;
-  let update = || ps::Process::run(command.as_slice()).map_err(aux_to_str);
-  let mut active = update()?;
+  let cwd = env::current_dir().map_err(aux_to_str)?;
+  let watch_dir = parsed_args.dir_of(parsed_args.watch_from).unwrap_or_else(|| cwd.clone());
+  let run_dir = parsed_args.dir_of(parsed_args.run_from);
+  if run_dir.is_some() {
+    // the script is given to a child running in another directory
+    let script = parsed_args.script.as_ref().and_then(|s| path::absolute(s).ok());
+    parsed_args.script = script.and_then(|s| s.into_os_string().into_string().ok());
+  }
+  let stages = parsed_args.command(&config.interpreters)?;
+  if let Some(script) = &parsed_args.script {
+    stages.build.iter().for_each(|step| eprintln!("Building with {}", step.join(" ")));
+    eprintln!("Running {}", stages.run.join(" "));
+    parsed_args.merge(get_directives(script, &stages.run).map_err(aux_to_str)?);
+  }
+  let (watch, ignore) = watch_and_ignore(&parsed_args, &watch_dir)?;
+  let ignore = match ignore {
+    Some(ignore) => Some(Arc::new(walk::Matcher::new(
+      &parse_selector_with(&ignore, aliases).map_err(|e| e.render())?, parsed_args.match_options()
+    )?)),
+    None => None
+  };
+  // the program may be the output of the build, so it is checked after building
+  let shebang_of = parsed_args.script.as_deref().filter(|_| stages.shebang);
+  let update = || {
+    check_program(&stages.run[0], run_dir.as_deref(), shebang_of)?;
+    ps::Process::run(stages.run.as_slice(), &parsed_args.env, run_dir.as_deref()).map_err(aux_to_str)
+  };
+  let rebuild = || build(&stages.build, &parsed_args.env, run_dir.as_deref(), shebang_of);
+  let mut active = if rebuild()? { Some(update()?) } else { None };
+  let restart = |active: &mut Option<ps::Process>| -> Result<(), String> {
+    if rebuild()? {
+      if let Some(mut previous) = active.take() {
+        previous.kill();
+      }
+      *active = Some(update()?);
+    }
+    Ok(())
+  };
   
-  let targets = walk::matches(parse_selector(&parsed_args.watch)?)?;
+  let selector = parse_selector_with(&watch, aliases).map_err(|e| e.render())?;
+  let watched = walk::Matcher::new(&selector, parsed_args.match_options())?;
+  let targets: Vec<_> = walk::matches(selector, parsed_args.match_options(), &watch_dir, ignore.clone())?.collect();
+  // a directory watched on its own reports changes of its entries, recursive watches also report hidden ones
+  let watched_dirs: HashSet<PathBuf> = targets.iter()
+    .filter(|(path, mode)| *mode == RecursiveMode::NonRecursive && path.is_dir())
+    .filter_map(|(path, _)| path::absolute(path).ok()).collect();
+  // the files the script imports, scanned again after each change
+  let scan_imports = || match (&parsed_args.script, parsed_args.follow_imports) {
+    (Some(script), true) => imports::follow(Path::new(script), &stages.run),
+    _ => Vec::new()
+  };
+  let mut imported = scan_imports();
   let mut last_run = SystemTime::now();
+  let delay = parsed_args.delay() as u128;
+  // after a failed restart, when to try again and the wait that led there
+  let mut retry: Option<(Instant, Duration)> = None;
 
-  for _ in watcher::WatchingChannel::try_new(targets).map_err(aux_to_str)? {
-    if last_run.elapsed().and_then(|x| Ok(x.as_millis())).unwrap_or(ELAPSE_TIME) >= ELAPSE_TIME {
-      last_run = SystemTime::now();
-      active.kill();
-      active = update()?;
+  'watching: loop {
+    let imported_targets = imported.iter().map(|p| (p.clone(), RecursiveMode::NonRecursive));
+    let channel = watcher::WatchingChannel::try_new(targets.iter().cloned().chain(imported_targets)).map_err(aux_to_str)?;
+    loop {
+      // no change means it is time to retry
+      let paths = match channel.recv_until(retry.map(|(at, _)| at)) {
+        Ok(paths) => Some(paths),
+        Err(RecvTimeoutError::Timeout) => None,
+        Err(RecvTimeoutError::Disconnected) => break 'watching
+      };
+      // event paths are absolute, relative selectors are matched from where they were walked
+      let relevant = |path: &PathBuf| imported.contains(path) || watched.is_match_from(path, &watch_dir)
+        || path.parent().is_some_and(|dir| watched_dirs.contains(dir));
+      let ignored = |path: &PathBuf| ignore.as_ref().is_some_and(|m| m.is_match_from(path, &watch_dir));
+      if paths.as_ref().is_some_and(|paths| !paths.is_empty() && !paths.iter().any(|p| relevant(p) && !ignored(p))) {
+        continue
+      }
+      if paths.is_none() || last_run.elapsed().map(|x| x.as_millis()).unwrap_or(delay) >= delay {
+        let restarted = restart(&mut active);
+        let wait = next_retry(restarted.is_ok(), retry.map(|(_, wait)| wait));
+        if let (Err(e), Some(wait)) = (restarted, wait) {
+          eprintln!("Restart failed, trying again in {}ms or on the next change: {}", wait.as_millis(), e);
+        }
+        retry = wait.map(|wait| (Instant::now() + wait, wait));
+        // changes made by the build itself are not new changes
+        last_run = SystemTime::now();
+      }
+      // also on skipped events, the change that added an import may still be being written
+      let rescanned = scan_imports();
+      if rescanned != imported {
+        imported = rescanned;
+        continue 'watching
+      }
     }
   }
   Ok(())
-}
\ No newline at end of file
+}
+
+fn main() -> ExitCode {
+  match run() {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(e) => {
+      eprintln!("{}", e);
+      ExitCode::FAILURE
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_next_retry() {
+    let mut wait = None;
+    let mut waits = Vec::new();
+    for _ in 0..9 {
+      wait = next_retry(false, wait);
+      waits.extend(wait.map(|w| w.as_millis()));
+    }
+    assert_eq!(waits, [500, 1000, 2000, 4000, 8000, 16000, 30000, 30000, 30000]);
+    assert_eq!(next_retry(true, wait), None);
+    assert_eq!(next_retry(false, next_retry(true, wait)), Some(FIRST_RETRY));
+  }
+}
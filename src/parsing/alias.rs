@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+
+/// Selectors shipped with reburn, used as `@name`
+const PRESETS: [(&str, &str); 4] = [
+  ("rust", "**/*.rs,Cargo.toml,Cargo.lock,build.rs"),
+  ("node", "{*,!node_modules*/**/*}.{js,mjs,cjs,jsx,ts,tsx,json}"),
+  ("python", "**/*.py,pyproject.toml,requirements*.txt,setup.{py,cfg}"),
+  ("go", "**/*.go,go.mod,go.sum"),
+];
+
+/// Named selectors that can be used inside any selector as `@name`
+#[derive(Debug, Clone)]
+pub struct Aliases(HashMap<String, String>);
+
+impl Default for Aliases {
+  fn default() -> Self {
+    Aliases(PRESETS.iter().map(|(name, pattern)| (name.to_string(), pattern.to_string())).collect())
+  }
+}
+
+impl Aliases {
+  /// Alias names use letters, digits, `_` and `-`
+  pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+  }
+
+  /// Adds an alias, replacing any preset with the same name
+  pub fn define(&mut self, name: &str, pattern: &str) {
+    self.0.insert(name.to_string(), pattern.to_string());
+  }
+
+  pub fn get(&self, name: &str) -> Option<&str> {
+    self.0.get(name).map(String::as_str)
+  }
+}
@@ -0,0 +1,66 @@
+use std::env;
+
+use super::selector_error::{SelectorError, SelectorErrorKind};
+
+/// Replaces `${VAR}` and `${VAR:-default}` in a pattern, the default is used when
+/// the variable is unset or empty
+pub fn interpolate_with<F: Fn(&str) -> Option<String>>(pattern: &str, lookup: F) -> Result<String, SelectorError> {
+  let fail = |kind, start, end| SelectorError { kind, span: start..end, pattern: pattern.to_string() };
+  let mut out = String::new();
+  let mut rest = 0;
+  while let Some(found) = pattern[rest..].find("${") {
+    let start = rest + found;
+    out.push_str(&pattern[rest..start]);
+    let end = match pattern[start..].find('}') {
+      Some(i) => start + i,
+      None => return Err(fail(SelectorErrorKind::UnclosedVariable, start, pattern.len()))
+    };
+    let inner = &pattern[start + 2..end];
+    let (name, default) = match inner.split_once(":-") {
+      Some((name, default)) => (name, Some(default)),
+      None => (inner, None)
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+      return Err(fail(SelectorErrorKind::InvalidVariable, start, end + 1))
+    }
+    match (lookup(name).filter(|v| !v.is_empty() || default.is_none()), default) {
+      (Some(value), _) => out.push_str(&value),
+      (None, Some(default)) => out.push_str(default),
+      (None, None) => return Err(fail(SelectorErrorKind::UnsetVariable, start, end + 1))
+    }
+    rest = end + 1;
+  }
+  out.push_str(&pattern[rest..]);
+  Ok(out)
+}
+
+pub fn interpolate(pattern: &str) -> Result<String, SelectorError> {
+  interpolate_with(pattern, |name| env::var(name).ok())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use SelectorErrorKind::*;
+
+  fn lookup(name: &str) -> Option<String> {
+    match name {
+      "OUT_DIR" => Some("target/out".to_string()),
+      "EMPTY" => Some(String::new()),
+      _ => None
+    }
+  }
+
+  #[test]
+  fn test_interpolate() {
+    let error = |p: &str| interpolate_with(p, lookup).map_err(|e| (e.kind, e.span)).unwrap_err();
+    assert_eq!(interpolate_with("${OUT_DIR}/**", lookup), Ok("target/out/**".to_string()));
+    assert_eq!(interpolate_with("a/${XDG:-~/.config}/b", lookup), Ok("a/~/.config/b".to_string()));
+    assert_eq!(interpolate_with("${EMPTY:-x}${EMPTY}", lookup), Ok("x".to_string()));
+    assert_eq!(interpolate_with("$OUT_DIR/$", lookup), Ok("$OUT_DIR/$".to_string()));
+    assert_eq!(error("src/${MISSING}/a"), (UnsetVariable, 4..14));
+    assert_eq!(error("${OUT_DIR"), (UnclosedVariable, 0..9));
+    assert_eq!(error("${A-B}"), (InvalidVariable, 0..6));
+    assert_eq!(error("${}"), (InvalidVariable, 0..3));
+  }
+}
@@ -1,70 +1,625 @@
-use std::{path::Path, fs::File, io::{Result, BufReader, BufRead, Error, ErrorKind}, vec};
+use std::{path::{Path, PathBuf}, fs::File, io::{self, BufReader, BufRead, Read, Error, ErrorKind}, env, collections::HashMap};
 
-fn get_reburn_name() -> String {
-  "reburn".to_string() // Should it be get dynamically
+/// Commands for scripts without a shebang, by extension
+const INTERPRETERS: [(&str, &[&str]); 7] = [
+  ("py", &["python3"]),
+  ("js", &["node"]),
+  ("mjs", &["node"]),
+  ("ts", &["deno", "run"]),
+  ("rb", &["ruby"]),
+  ("sh", &["sh"]),
+  ("lua", &["lua"]),
+];
+
+/// Settings from `reburn: key=value` comments in the script
+#[derive(Debug, Default, PartialEq)]
+pub struct ScriptConfig {
+  pub watch: Option<String>,
+  pub ignore: Option<String>,
+  pub delay: Option<u64>,
+  pub env: Vec<(String, String)>
+}
+
+/// Line comment of the script language, from its extension or else its interpreter
+fn comment_prefix(source: &Path, interpreter: &[String]) -> &'static str {
+  let extension = source.extension().and_then(|e| e.to_str()).unwrap_or_default();
+  let program = interpreter.first().and_then(|p| Path::new(p).file_stem()).and_then(|p| p.to_str()).unwrap_or_default();
+  // through env the interpreter is the next word
+  let program = if program == "env" { interpreter.get(1).map(String::as_str).unwrap_or_default() } else { program };
+  match (extension, program) {
+    ("js" | "mjs" | "cjs" | "ts" | "go" | "rs" | "c" | "cpp" | "java" | "kt" | "swift" | "dart" | "php", _) => "//",
+    ("lua" | "sql" | "hs", _) => "--",
+    ("", "node" | "deno" | "bun" | "ts-node" | "php") => "//",
+    ("", "lua" | "runghc") => "--",
+    _ => "#"
+  }
+}
+
+fn parse_directives(source: &str, prefix: &str) -> Result<ScriptConfig, String> {
+  let mut config = ScriptConfig::default();
+  for (n, line) in source.lines().enumerate() {
+    let Some(directive) = line.trim_start().strip_prefix(prefix)
+      .and_then(|l| l.trim_start().strip_prefix("reburn:")).map(str::trim) else {
+      continue
+    };
+    let fail = |message: &str| Err(format!("Directive on line {}: {}", n + 1, message));
+    let Some((key, value)) = directive.split_once('=') else {
+      return fail("expected `key=value`")
+    };
+    let value = value.trim();
+    match key.trim() {
+      "watch" => config.watch = Some(value.to_string()),
+      "ignore" => config.ignore = Some(value.to_string()),
+      "delay" => match value.parse() {
+        Ok(delay) => config.delay = Some(delay),
+        Err(_) => return fail("the delay is a number of milliseconds")
+      },
+      "env" => match value.split_once('=') {
+        Some((name, value)) if !name.is_empty() => config.env.push((name.to_string(), value.to_string())),
+        _ => return fail("expected `env=NAME=value`")
+      },
+      other => return fail(&format!("unknown key `{}`", other))
+    }
+  }
+  Ok(config)
+}
+
+/// Most bytes of a script read looking for directives
+const MAX_DIRECTIVES_BYTES: u64 = 1 << 20;
+
+/// The lines of `bytes` that are text, the others are left empty so the lines keep their numbers
+fn text_lines(bytes: &[u8]) -> String {
+  let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+  bytes.split(|b| *b == b'\n').map(|line| match std::str::from_utf8(line) {
+    Ok(line) if !line.contains('\0') => line,
+    _ => ""
+  }).collect::<Vec<_>>().join("\n")
+}
+
+/// Reads the `reburn:` comments of the script, `interpreter` tells the language when the extension does not
+pub fn get_directives<S: AsRef<Path>>(source: S, interpreter: &[String]) -> io::Result<ScriptConfig> {
+  let source = source.as_ref();
+  let in_script = |kind, e: &dyn ToString| Error::new(kind, format!("{}: {}", source.display(), e.to_string()));
+  let mut bytes = Vec::new();
+  File::open(source).and_then(|f| f.take(MAX_DIRECTIVES_BYTES).read_to_end(&mut bytes)).map_err(|e| in_script(e.kind(), &e))?;
+  parse_directives(&text_lines(&bytes), comment_prefix(source, interpreter)).map_err(|e| in_script(ErrorKind::InvalidData, &e))
+}
+
+/// Interpreters used by extension when the script has no shebang
+#[derive(Debug, Clone)]
+pub struct Interpreters(HashMap<String, Vec<String>>);
+
+impl Default for Interpreters {
+  fn default() -> Self {
+    Interpreters(INTERPRETERS.iter().map(|(ext, command)| {
+      (ext.to_string(), command.iter().map(|w| w.to_string()).collect())
+    }).collect())
+  }
+}
+
+impl Interpreters {
+  /// Sets the command for an extension, written with or without the dot
+  pub fn define(&mut self, extension: &str, command: Vec<String>) {
+    self.0.insert(extension.trim_start_matches('.').to_string(), command);
+  }
+
+  /// The command for the extension of the script
+  pub fn of<S: AsRef<Path>>(&self, source: S) -> Option<Vec<String>> {
+    let extension = source.as_ref().extension()?.to_str()?;
+    self.0.get(extension).cloned()
+  }
+}
+
+/// How reburn can be written in a shebang
+struct ReburnName {
+  /// File names, with or without `.exe`
+  names: Vec<String>,
+  /// The running executable, to recognize it under any path
+  exe: Option<PathBuf>
+}
+
+impl ReburnName {
+  fn current() -> Self {
+    let exe = env::current_exe().ok().and_then(|p| p.canonicalize().ok());
+    let mut names = vec!["reburn".to_string()];
+    names.extend(exe.as_ref().and_then(|e| e.file_stem()).and_then(|n| n.to_str()).map(String::from));
+    ReburnName { names, exe }
+  }
+
+  fn matches(&self, program: &str) -> bool {
+    let path = Path::new(program);
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    self.names.iter().any(|n| name == n || name.strip_suffix(".exe") == Some(n))
+      || self.exe.as_ref().is_some_and(|exe| path.canonicalize().is_ok_and(|p| p == *exe))
+  }
 }
 
 #[derive(Debug, PartialEq)]
 enum ShebangLine {
-  Shebang(String, Option<String>),
-  ReburnShebang,
+  /// The interpreter followed by its arguments
+  Shebang(Vec<String>),
+  /// The arguments for reburn itself
+  ReburnShebang(Vec<String>),
+  /// `#!build`, a step before running
+  Build(Vec<String>),
+  /// `#!run`, the program to run instead of the script
+  Run(Vec<String>),
   NoShebang
 }
 
 use ShebangLine::*;
 
-fn shebang_of<'a>(line: &'a str, reburn_name: &'a str) -> ShebangLine {
-  if !line.starts_with("#!") {
-    return NoShebang;
-  }
-  let mut chars = line[2..].trim_start().chars();
-  let name: String = chars.by_ref().take_while(|s| *s != ' ').collect::<String>().trim().to_string();
-  if name.is_empty() {
-    return NoShebang;
-  }
-  if name == *reburn_name {
-    return ReburnShebang;
-  }
-  let remain: String = chars.collect();
-  let trimmed = remain.trim().to_string();
-  Shebang(name, if trimmed.is_empty() { None } else { Some(trimmed) })
-}
-
-pub fn get_interpreter<S: AsRef<Path>>(source: S) -> Result<Vec<String>> {
-  let file = File::open(source.as_ref())?;
-  let mut buf = BufReader::new(file);
-  let reburn_name = get_reburn_name();
-  let mut current: ShebangLine;
-  loop {
-    let mut line = String::new();
-    let read = buf.read_line(&mut line)?;
-    current = shebang_of(&line, &reburn_name);
-    if read == 0 || current != ReburnShebang {
-      break;
+/// What follows a backslash, `None` for `\c` which ends the string
+fn escaped(c: char, quoted: bool) -> Result<Option<char>, String> {
+  Ok(Some(match c {
+    '\\' | '"' | '\'' | '$' | '#' => c,
+    '_' => ' ',
+    'f' => '\x0c',
+    'n' => '\n',
+    'r' => '\r',
+    't' => '\t',
+    'v' => '\x0b',
+    'c' if !quoted => return Ok(None),
+    'c' => return Err("`\\c` can not be inside double quotes".to_string()),
+    c => return Err(format!("invalid escape `\\{}`", c))
+  }))
+}
+
+/// Splits the arguments of a shebang line the way `env -S` does: quotes, backslash escapes,
+/// `${VAR}` expansion and `#` comments
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+  split_words_with(line, |name| env::var(name).ok())
+}
+
+/// Like `split_words`, `lookup` gives the value of `${VAR}`
+fn split_words_with<F: Fn(&str) -> Option<String>>(line: &str, lookup: F) -> Result<Vec<String>, String> {
+  let mut words = Vec::new();
+  let mut word: Option<String> = None;
+  let mut chars = line.chars().peekable();
+  let mut quote = None;
+  while let Some(c) = chars.next() {
+    match (quote, c) {
+      (None, c) if c.is_whitespace() => words.extend(word.take()),
+      (None, '#') if word.is_none() => break,
+      (None, '\'' | '"') => {
+        quote = Some(c);
+        word.get_or_insert_with(String::new);
+      },
+      (Some(q), c) if q == c => quote = None,
+      // only \\ and \' are escapes inside single quotes
+      (Some('\''), '\\') if matches!(chars.peek(), Some('\\' | '\'')) =>
+        word.get_or_insert_with(String::new).extend(chars.next()),
+      (Some('\''), '\\') if chars.peek() == Some(&'c') => break,
+      (Some('\''), c) => word.get_or_insert_with(String::new).push(c),
+      (_, '\\') => match escaped(chars.next().ok_or("missing escaped character")?, quote.is_some())? {
+        // an unquoted `\_` separates words
+        Some(' ') if quote.is_none() => words.extend(word.take()),
+        Some(c) => word.get_or_insert_with(String::new).push(c),
+        None => break
+      },
+      (_, '$') => {
+        if chars.next() != Some('{') {
+          return Err("only `${VAR}` expansion is supported".to_string())
+        }
+        let name: String = chars.by_ref().take_while(|c| *c != '}').collect();
+        word.get_or_insert_with(String::new).push_str(&lookup(&name).unwrap_or_default());
+      },
+      (_, c) => word.get_or_insert_with(String::new).push(c),
+    }
+  }
+  if quote.is_some() {
+    return Err("no terminating quote".to_string())
+  }
+  words.extend(word);
+  Ok(words)
+}
+
+fn is_env(program: &str) -> bool {
+  Path::new(program).file_name().is_some_and(|n| n == "env")
+}
+
+/// `env -S` splits its argument, the words we already split are what it would run
+fn without_split_flag(mut words: Vec<String>) -> Vec<String> {
+  if words.first().is_some_and(|w| is_env(w)) && words.len() > 1 {
+    if words[1] == "-S" || words[1] == "--split-string" {
+      words.remove(1);
+    } else if let Some(rest) = words[1].strip_prefix("-S").or_else(|| words[1].strip_prefix("--split-string=")) {
+      words[1] = rest.to_string();
+    }
+  }
+  words
+}
+
+/// `//!` and `--!` work like `#!` for languages without `#` comments, but only when the
+/// first line is the reburn shebang, the kernel never runs them
+const SHEBANG_PREFIXES: [&str; 3] = ["#!", "//!", "--!"];
+
+fn shebang_of(line: &str, prefix: &str, reburn: &ReburnName) -> Result<ShebangLine, String> {
+  let Some(line) = line.strip_prefix(prefix) else {
+    return Ok(NoShebang);
+  };
+  let words = without_split_flag(split_words(line).map_err(|e| format!("Invalid shebang: {}", e))?);
+  // reburn may be found through env
+  let program = if words.len() > 1 && is_env(&words[0]) { 1 } else { 0 };
+  Ok(match words.get(program) {
+    None => NoShebang,
+    Some(name) if reburn.matches(name) => ReburnShebang(words[program + 1..].to_vec()),
+    Some(_) => Shebang(words)
+  })
+}
+
+/// Whether the words of a line that can not be split still start with reburn, directly or through env
+fn names_reburn(line: &str, reburn: &ReburnName) -> bool {
+  let mut words = line.split_whitespace();
+  match words.next() {
+    Some(word) if is_env(word) => words.find(|w| !w.starts_with('-')).is_some_and(|w| reburn.matches(w)),
+    Some(word) => reburn.matches(word),
+    None => false
+  }
+}
+
+/// The shebang in the first line of a script, with the prefix used by the lines after it
+fn first_shebang(line: &str, reburn: &ReburnName) -> Result<(ShebangLine, &'static str), String> {
+  for prefix in SHEBANG_PREFIXES {
+    if let Some(rest) = line.strip_prefix(prefix) {
+      return Ok(match shebang_of(line, prefix, reburn) {
+        Ok(shebang @ ReburnShebang(_)) => (shebang, prefix),
+        Ok(shebang) if prefix == "#!" => (shebang, prefix),
+        Err(e) if prefix == "#!" || names_reburn(rest, reburn) => return Err(e),
+        // any other `//!` or `--!` line is an ordinary comment
+        _ => (NoShebang, prefix)
+      })
+    }
+  }
+  Ok((NoShebang, "#!"))
+}
+
+/// `#!build` and `#!run` lines, `$SCRIPT` is replaced by the path of the script
+fn stage_of(line: &str, prefix: &str, script: &str) -> Result<Option<ShebangLine>, String> {
+  let Some(rest) = line.strip_prefix(prefix).map(str::trim_start) else {
+    return Ok(None)
+  };
+  let (kind, command) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+  if kind != "build" && kind != "run" {
+    return Ok(None)
+  }
+  let lookup = |name: &str| if name == "SCRIPT" { Some(script.to_string()) } else { env::var(name).ok() };
+  let words = split_words_with(&command.replace("$SCRIPT", "${SCRIPT}"), lookup)
+    .map_err(|e| format!("Invalid {} shebang: {}", kind, e))?;
+  if words.is_empty() {
+    return Err(format!("Empty {} shebang", kind))
+  }
+  Ok(Some(if kind == "build" { Build(words) } else { Run(words) }))
+}
+
+/// Longest shebang line read, a longer one means the file is not a script
+const MAX_LINE_LENGTH: usize = 4096;
+/// Most lines read looking for the shebangs
+const MAX_SHEBANG_LINES: usize = 64;
+
+/// Lines at the top of a script without the UTF-8 BOM and `\r\n` endings. Shebang lines that are binary
+/// or too long fail, and so do too many of them, instead of reading the whole file
+struct HeaderLines<R> {
+  reader: R,
+  read: usize
+}
+
+impl<R: BufRead> HeaderLines<R> {
+  fn new(reader: R) -> Self {
+    HeaderLines { reader, read: 0 }
+  }
+
+  fn read_line(&mut self) -> io::Result<Option<String>> {
+    let invalid = |message: String| Err(Error::new(ErrorKind::InvalidData, message));
+    let n = self.read + 1;
+    if n > MAX_SHEBANG_LINES {
+      return invalid(format!("More than {} shebang lines at the top of the script", MAX_SHEBANG_LINES))
+    }
+    let mut bytes = Vec::new();
+    (&mut self.reader).take(MAX_LINE_LENGTH as u64 + 1).read_until(b'\n', &mut bytes)?;
+    if bytes.is_empty() {
+      return Ok(None)
+    }
+    self.read = n;
+    let too_long = !bytes.ends_with(b"\n") && bytes.len() > MAX_LINE_LENGTH;
+    if bytes.ends_with(b"\n") {
+      bytes.pop();
+      if bytes.ends_with(b"\r") {
+        bytes.pop();
+      }
+    }
+    if n == 1 && bytes.starts_with(b"\xef\xbb\xbf") {
+      bytes.drain(..3);
+    }
+    // any other line ends the shebangs, it may be minified code or text in another encoding
+    if !SHEBANG_PREFIXES.iter().any(|p| bytes.starts_with(p.as_bytes())) {
+      return Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+    if bytes.contains(&0) {
+      return invalid(format!("Line {} of the script has a NUL byte, it looks like a binary file", n))
+    }
+    if too_long {
+      return invalid(format!("Line {} of the script is longer than {} bytes", n, MAX_LINE_LENGTH))
+    }
+    match String::from_utf8(bytes) {
+      Ok(line) => Ok(Some(line)),
+      Err(_) => invalid(format!("Line {} of the script is not valid UTF-8, it looks like a binary file", n))
     }
   }
-  match current {
-    Shebang(a, b) => if let Some(b) = b {
-      Ok(vec![a, b])
-    } else {
-      Ok(vec![a])
-    },
-    ReburnShebang => Err(Error::new(ErrorKind::NotFound, "Only the reburn shebang found")),
-    NoShebang => Err(Error::new(ErrorKind::NotFound, "No shebang found")),
+}
+
+impl<R: BufRead> Iterator for HeaderLines<R> {
+  type Item = io::Result<String>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.read_line().transpose()
+  }
+}
+
+/// The build steps and the command of the shebangs at the top of a script, after any reburn shebang
+fn find_shebang<I: Iterator<Item = io::Result<String>>>(
+  mut lines: I, reburn: &ReburnName, script: &str
+) -> io::Result<(Vec<Vec<String>>, ShebangLine)> {
+  let invalid = |e| Error::new(ErrorKind::InvalidData, e);
+  let mut build = Vec::new();
+  let Some(first) = lines.next().transpose()? else {
+    return Ok((build, NoShebang))
+  };
+  let (mut current, prefix) = first_shebang(&first, reburn).map_err(invalid)?;
+  while matches!(current, ReburnShebang(_) | Build(_)) {
+    if let Build(step) = current {
+      build.push(step);
+    }
+    current = match lines.next().transpose()? {
+      Some(line) => match stage_of(&line, prefix, script).map_err(invalid)? {
+        Some(stage) => stage,
+        None => shebang_of(&line, prefix, reburn).map_err(invalid)?
+      },
+      None => NoShebang
+    };
   }
+  Ok((build, current))
+}
+
+/// What the kernel passes as the only argument of the interpreter, the rest of the line as written
+fn raw_argument(line: &str) -> &str {
+  SHEBANG_PREFIXES.iter().find_map(|p| line.strip_prefix(p)).unwrap_or(line).trim_start().split_once(char::is_whitespace).map(|(_, rest)| rest.trim()).unwrap_or("")
+}
+
+/// The arguments of the reburn shebang in the first line of the script, and the same
+/// arguments as the kernel passes them
+pub fn get_reburn_args<S: AsRef<Path>>(source: S) -> io::Result<Option<(Vec<String>, String)>> {
+  let Some(line) = HeaderLines::new(BufReader::new(File::open(source.as_ref())?)).next().transpose()? else {
+    return Ok(None)
+  };
+  Ok(match first_shebang(&line, &ReburnName::current()) {
+    Ok((ReburnShebang(args), _)) => Some((args, raw_argument(&line).to_string())),
+    _ => None
+  })
+}
+
+/// Commands to build and run a script
+#[derive(Debug, PartialEq, Default)]
+pub struct Stages {
+  /// Run in order before the program, it is only restarted when all of them succeed
+  pub build: Vec<Vec<String>>,
+  pub run: Vec<String>,
+  /// The commands come from the shebangs of the script and not from its extension
+  pub shebang: bool
+}
+
+/// The commands from the script shebangs, the interpreter may come from its extension if it has none.
+/// The errors name the script
+pub fn get_stages(script: &str, interpreters: &Interpreters) -> io::Result<Stages> {
+  let in_script = |e: Error| Error::new(e.kind(), format!("{}: {}", script, e));
+  let file = File::open(script).map_err(in_script)?;
+  let with_script = |mut v: Vec<String>| {
+    v.push(script.to_string());
+    v
+  };
+  let found = find_shebang(HeaderLines::new(BufReader::new(file)), &ReburnName::current(), script).map_err(in_script)?;
+  let (build, run, shebang) = match found {
+    (build, Run(v)) => (build, v, true),
+    (build, Shebang(v)) => (build, with_script(v), true),
+    (build, _) if !build.is_empty() => return Err(in_script(Error::new(
+      ErrorKind::NotFound, "A build shebang must be followed by a run shebang or an interpreter"
+    ))),
+    (build, _) => (build, with_script(interpreters.of(script).ok_or_else(|| in_script(Error::new(
+      ErrorKind::NotFound, "No shebang found and no interpreter known for the extension"
+    )))?), false)
+  };
+  Ok(Stages { build, run, shebang })
 }
 
 #[cfg(test)]
 mod tests {
+  use std::fs;
+  use crate::testing::TempDir;
   use super::*;
 
+  fn words(v: &[&str]) -> ShebangLine {
+    Shebang(v.iter().map(|w| w.to_string()).collect())
+  }
+
   #[test]
   fn test_shebang_of() {
-    assert_eq!(shebang_of("nothing", "reburn"), NoShebang);
-    assert_eq!(shebang_of("#!reburn", "reburn"), ReburnShebang);
-    assert_eq!(shebang_of("#!  reburn arg", "reburn"), ReburnShebang);
-    assert_eq!(shebang_of("#!smt", "reburn"), Shebang("smt".to_string(), None));
-    assert_eq!(shebang_of("#!smt else", "reburn"), Shebang("smt".to_string(), Some("else".to_string())));
-    assert_eq!(shebang_of("#!  smt  else  ", "reburn"), Shebang("smt".to_string(), Some("else".to_string())));
-  }
-}
\ No newline at end of file
+    let reburn = ReburnName::current();
+    let shebang_of = |line: &str| first_shebang(line, &reburn).unwrap().0;
+    let args = |v: &[&str]| ReburnShebang(v.iter().map(|w| w.to_string()).collect());
+    assert_eq!(shebang_of("nothing"), NoShebang);
+    assert_eq!(shebang_of("#!"), NoShebang);
+    assert_eq!(shebang_of("#!reburn"), ReburnShebang(vec![]));
+    assert_eq!(shebang_of("#!  reburn arg"), ReburnShebang(vec!["arg".to_string()]));
+    assert_eq!(shebang_of("#!reburn --delay 200 \"{a.py,lib/**}\""), ReburnShebang(
+      vec!["--delay".to_string(), "200".to_string(), "{a.py,lib/**}".to_string()]
+    ));
+    assert_eq!(shebang_of("#!/usr/local/bin/reburn src/**"), args(&["src/**"]));
+    assert_eq!(shebang_of("#!/opt/reburn.exe"), args(&[]));
+    assert_eq!(shebang_of("#!/usr/bin/env reburn"), args(&[]));
+    assert_eq!(shebang_of("#!/usr/bin/env -S reburn --delay 200"), args(&["--delay", "200"]));
+    let exe = env::current_exe().unwrap();
+    assert_eq!(shebang_of(&format!("#!{} a", exe.display())), args(&["a"]));
+    assert_eq!(shebang_of(&format!("#!/usr/bin/env {}", exe.file_name().unwrap().to_str().unwrap())), args(&[]));
+    assert_eq!(shebang_of("#!/usr/bin/reburner"), words(&["/usr/bin/reburner"]));
+    assert_eq!(shebang_of("#!/usr/bin/env"), words(&["/usr/bin/env"]));
+    assert_eq!(shebang_of("#!smt"), words(&["smt"]));
+    assert_eq!(shebang_of("#!smt else"), words(&["smt", "else"]));
+    assert_eq!(shebang_of("#!  smt  else  \n"), words(&["smt", "else"]));
+    assert_eq!(shebang_of("#!/usr/bin/env python3 -u"), words(&["/usr/bin/env", "python3", "-u"]));
+    assert_eq!(shebang_of("#!/usr/bin/env -S deno run --allow-net"), words(&["/usr/bin/env", "deno", "run", "--allow-net"]));
+    assert_eq!(shebang_of("#!/usr/bin/env -Snode --split-string=x"), words(&["/usr/bin/env", "node", "--split-string=x"]));
+    assert_eq!(shebang_of("#!/bin/sh -c 'echo \"a b\"' \"c\\\"d\" e\\_f # g"), words(&["/bin/sh", "-c", "echo \"a b\"", "c\"d", "e", "f"]));
+    assert_eq!(shebang_of("#!a '' \"\" 'x\\ny' a#b \\c d"), words(&["a", "", "", "x\\ny", "a#b"]));
+    assert!(first_shebang("#!a 'b", &reburn).is_err());
+    assert!(first_shebang("#!a \\q", &reburn).is_err());
+    assert!(first_shebang("#!a $HOME", &reburn).is_err());
+  }
+
+  #[test]
+  fn test_find_shebang() {
+    let reburn = ReburnName::current();
+    let find = |source: &str| find_shebang(source.lines().map(|l| Ok(l.to_string())), &reburn, "a b.rs").unwrap().1;
+    let words = |v: &[&str]| Shebang(v.iter().map(|w| w.to_string()).collect());
+    assert_eq!(find("#!reburn\n#!reburn x\n#!python3 -u\n"), words(&["python3", "-u"]));
+    assert_eq!(find("//!reburn *.js\n//!node\nconsole.log(1)"), words(&["node"]));
+    assert_eq!(find("--!reburn\n--!lua\n"), words(&["lua"]));
+    assert_eq!(find("//!node\n"), NoShebang);
+    assert_eq!(find("//! it's a server\nconsole.log(1)"), NoShebang);
+    assert_eq!(find("--! don't\n"), NoShebang);
+    assert!(find_shebang(["//!reburn 'x"].into_iter().map(|l| Ok(l.to_string())), &reburn, "a").is_err());
+    assert!(find_shebang(["//!/usr/bin/env -S reburn 'x"].into_iter().map(|l| Ok(l.to_string())), &reburn, "a").is_err());
+    assert!(find_shebang(["#!python 'x"].into_iter().map(|l| Ok(l.to_string())), &reburn, "a").is_err());
+    assert_eq!(find("//!reburn\n#!node\n"), NoShebang);
+    assert_eq!(find("#!reburn\nprint(1)\n"), NoShebang);
+    assert_eq!(find(""), NoShebang);
+    let strings = |v: &[&str]| v.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+    let stages = |source: &str| find_shebang(source.lines().map(|l| Ok(l.to_string())), &reburn, "a b.rs").unwrap();
+    assert_eq!(stages("#!reburn\n#!build rustc -o /tmp/x $SCRIPT\n#!build strip /tmp/x\n#!run /tmp/x ${SCRIPT}\n"), (
+      vec![strings(&["rustc", "-o", "/tmp/x", "a b.rs"]), strings(&["strip", "/tmp/x"])], Run(strings(&["/tmp/x", "a b.rs"]))
+    ));
+    assert_eq!(stages("//!reburn\n//!build tsc $SCRIPT\n//!node\n"), (vec![strings(&["tsc", "a b.rs"])], words(&["node"])));
+    assert_eq!(stages("#!build x\n"), (vec![], words(&["build", "x"])));
+    assert!(find_shebang(["#!reburn", "#!run"].into_iter().map(|l| Ok(l.to_string())), &reburn, "a").is_err());
+  }
+
+  #[test]
+  fn test_interpreters() {
+    let mut interpreters = Interpreters::default();
+    assert_eq!(interpreters.of("server.js"), Some(vec!["node".to_string()]));
+    assert_eq!(interpreters.of("main.ts"), Some(vec!["deno".to_string(), "run".to_string()]));
+    assert_eq!(interpreters.of("main"), None);
+    interpreters.define(".py", vec!["python3.12".to_string(), "-u".to_string()]);
+    assert_eq!(interpreters.of("a/main.py"), Some(vec!["python3.12".to_string(), "-u".to_string()]));
+  }
+
+  #[test]
+  fn test_directives() {
+    let source = "#!reburn\n#!python\n# reburn: watch={app.py,lib/**/*.py}\n  #reburn: env = PORT=8080\n# reburn: delay=300\n# other: x\n";
+    assert_eq!(parse_directives(source, "#"), Ok(ScriptConfig {
+      watch: Some("{app.py,lib/**/*.py}".to_string()), ignore: None, delay: Some(300),
+      env: vec![("PORT".to_string(), "8080".to_string())]
+    }));
+    assert_eq!(parse_directives("// reburn: ignore=dist/**\n# reburn: delay=1", "//").map(|c| c.ignore), Ok(Some("dist/**".to_string())));
+    assert_eq!(parse_directives("\n-- reburn: delay=soon", "--"), Err("Directive on line 2: the delay is a number of milliseconds".to_string()));
+    assert_eq!(parse_directives("# reburn: env=PORT", "#"), Err("Directive on line 1: expected `env=NAME=value`".to_string()));
+    assert_eq!(parse_directives("# reburn: colour=red", "#"), Err("Directive on line 1: unknown key `colour`".to_string()));
+    assert_eq!(text_lines(b"\xef\xbb\xbf# a\r\nx = \"caf\xe9\"\n\0\n# reburn: delay=1"), "# a\r\n\n\n# reburn: delay=1");
+    let dir = TempDir::new("directives");
+    let script = dir.join("script.py");
+    fs::write(&script, b"#!python3\nx = \"caf\xe9\"\n# reburn: delay=5\n").unwrap();
+    assert_eq!(get_directives(&script, &[]).map(|c| c.delay).map_err(|e| e.to_string()), Ok(Some(5)));
+    fs::write(&script, b"\xff\n# reburn: delay=soon\n").unwrap();
+    assert_eq!(get_directives(&script, &[]).map_err(|e| e.to_string()), Err(format!(
+      "{}: Directive on line 2: the delay is a number of milliseconds", script.display()
+    )));
+    let words = |v: &[&str]| v.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+    assert_eq!(comment_prefix(Path::new("a.py"), &[]), "#");
+    assert_eq!(comment_prefix(Path::new("a.mjs"), &[]), "//");
+    assert_eq!(comment_prefix(Path::new("a.lua"), &[]), "--");
+    assert_eq!(comment_prefix(Path::new("server"), &words(&["/usr/bin/env", "node"])), "//");
+    assert_eq!(comment_prefix(Path::new("tool"), &words(&["/usr/bin/lua"])), "--");
+  }
+
+  #[test]
+  fn test_header_lines() {
+    let lines = |source: &[u8]| HeaderLines::new(source).collect::<io::Result<Vec<_>>>().map_err(|e| e.to_string());
+    assert_eq!(lines(b"\xef\xbb\xbf#!reburn\r\n#!python\r\n\xef\xbb\xbf"), Ok(vec![
+      "#!reburn".to_string(), "#!python".to_string(), "\u{feff}".to_string()
+    ]));
+    assert_eq!(lines(b"#!reburn\n#!\x7fELF\0\x02"), Err("Line 2 of the script has a NUL byte, it looks like a binary file".to_string()));
+    assert_eq!(lines(b"#!reburn \xff\n"), Err("Line 1 of the script is not valid UTF-8, it looks like a binary file".to_string()));
+    let mut long = b"#!".to_vec();
+    long.resize(MAX_LINE_LENGTH + 1, b'a');
+    assert_eq!(lines(&long), Err(format!("Line 1 of the script is longer than {} bytes", MAX_LINE_LENGTH)));
+    assert_eq!(lines(&long[1..]).map(|l| l.len()), Ok(1));
+    // lines that are not shebangs are left to the language, even minified or in another encoding
+    assert_eq!(lines(&[b'a'; MAX_LINE_LENGTH + 1]).map(|l| l.len()), Ok(1));
+    assert_eq!(lines(b"x = \"caf\xe9\"\n\x7fELF\0"), Ok(vec!["x = \"caf\u{fffd}\"".to_string(), "\u{7f}ELF\0".to_string()]));
+    let many = "#!reburn\n".repeat(MAX_SHEBANG_LINES + 1);
+    assert_eq!(lines(many.as_bytes()), Err(format!("More than {} shebang lines at the top of the script", MAX_SHEBANG_LINES)));
+    let reburn = ReburnName::current();
+    let (_, run) = find_shebang(HeaderLines::new(&b"\xef\xbb\xbf#!reburn\r\n#!python3 -u\r\n"[..]), &reburn, "a.py").unwrap();
+    assert_eq!(run, Shebang(vec!["python3".to_string(), "-u".to_string()]));
+  }
+
+  #[test]
+  fn test_get_stages() {
+    let dir = TempDir::new("stages");
+    let interpreters = Interpreters::default();
+    let stages = |name: &str, source: &[u8]| {
+      let script = dir.join(name);
+      fs::write(&script, source).unwrap();
+      get_stages(script.to_str().unwrap(), &interpreters).map(|s| s.run).map_err(|e| e.to_string())
+    };
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    let minified = [b'x'; MAX_LINE_LENGTH * 2];
+    assert_eq!(stages("min.js", &minified), Ok(vec!["node".to_string(), path("min.js")]));
+    assert_eq!(stages("lat.py", b"print(\"caf\xe9\")\n"), Ok(vec!["python3".to_string(), path("lat.py")]));
+    assert_eq!(stages("bin.py", b"#!reburn\n#!\0"), Err(format!(
+      "{}: Line 2 of the script has a NUL byte, it looks like a binary file", path("bin.py")
+    )));
+    assert_eq!(stages("tool", b"echo"), Err(format!(
+      "{}: No shebang found and no interpreter known for the extension", path("tool")
+    )));
+  }
+
+  #[test]
+  fn test_raw_argument() {
+    assert_eq!(raw_argument("#!reburn\n"), "");
+    assert_eq!(raw_argument("//!reburn a\n"), "a");
+    assert_eq!(raw_argument("#! reburn  --delay 200 \"a b\" \n"), "--delay 200 \"a b\"");
+  }
+
+  /// Runs a script through the kernel and through the split arguments, both must see the same argv
+  #[cfg(target_os = "linux")]
+  #[test]
+  fn test_split_like_env() {
+    use std::{fs, process::Command, os::unix::fs::PermissionsExt};
+
+    let dir = TempDir::new("shebang");
+    let cases = [
+      "a 'b c' \"d \\\"e\\\"\" f\\_g",
+      "'x\\'y' 'x\\\\y' 'x\\ny' \"a\\tb\" a#b #c",
+      "${HOME} \"${HOME}/x\" '${HOME}' \"\" ''",
+    ];
+    for (i, args) in cases.iter().enumerate() {
+      let script = dir.join(format!("script{}", i));
+      fs::write(&script, format!(
+        "#!/usr/bin/env -S sh -c 'for a; do printf \"[%s]\\\\n\" \"\\$a\"; done' zero {}\n", args
+      )).unwrap();
+      fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+      let run = |command: &mut Command| loop {
+        match command.output() {
+          // another test may be forking while the script is still open for writing
+          Err(e) if e.raw_os_error() == Some(26) => continue,
+          result => break result.unwrap()
+        }
+      };
+      let kernel = run(&mut Command::new(&script));
+      let stages = get_stages(script.to_str().unwrap(), &Interpreters::default()).unwrap();
+      let ours = run(Command::new(&stages.run[0]).args(&stages.run[1..]));
+      assert!(kernel.status.success(), "{}", String::from_utf8_lossy(&kernel.stderr));
+      assert_eq!(String::from_utf8_lossy(&kernel.stdout), String::from_utf8_lossy(&ours.stdout));
+    }
+  }
+}
@@ -1,3 +1,6 @@
 pub mod interpreter;
 mod selector_tokens;
+pub mod selector_error;
+mod interpolation;
+pub mod alias;
 pub mod selector;
\ No newline at end of file
@@ -1,47 +1,140 @@
-use std::rc::Rc;
+use std::{rc::Rc, fmt};
 
+pub use super::selector_tokens::Span;
 use super::selector_tokens::Token;
+use super::selector_error::{SelectorError, SelectorErrorKind};
+use super::interpolation::interpolate;
+use super::alias::Aliases;
+use crate::walk;
 
+/// Biggest number of options a range can expand to
+const MAX_RANGE: u64 = 10000;
+
+/// Deepest a bounded `**` can reach, each depth up to it is a route of its own
+const MAX_DEPTH: usize = 64;
+
+/// How names are compared, set globally or inline with `(?i)` and `(?h)`
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct MatchOptions {
+  /// i: ignore case
+  pub ignore_case: bool,
+  /// h: `*` and `**` also match names starting with a dot
+  pub hidden: bool
+}
+
+impl MatchOptions {
+  fn parse(flags: &str) -> Option<Self> {
+    let flags = flags.strip_prefix("(?")?.strip_suffix(')').filter(|f| !f.is_empty())?;
+    let mut options = MatchOptions::default();
+    for c in flags.chars() {
+      match c {
+        'i' => options.ignore_case = true,
+        'h' => options.hidden = true,
+        _ => return None
+      }
+    }
+    Some(options)
+  }
+
+  /// Options enabled in either side
+  pub fn union(self, other: MatchOptions) -> MatchOptions {
+    MatchOptions {
+      ignore_case: self.ignore_case || other.ignore_case,
+      hidden: self.hidden || other.hidden
+    }
+  }
+}
+
+impl fmt::Display for MatchOptions {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "(?{}{})", if self.ignore_case { "i" } else { "" }, if self.hidden { "h" } else { "" })
+  }
+}
+
+/// The spans of routes, concatenations, negations and aliases point errors found when expanding them
 #[derive(Debug, PartialEq, Clone)]
 pub enum Selector {
   /// {a,b}
   Option(Vec<Selector>),
   /// a/b/c
-  Route(Vec<Selector>),
+  Route(Vec<Selector>, Span),
   /// abc
-  Concat(Vec<Selector>),
+  Concat(Vec<Selector>, Span),
   /// *
   WildCard,
   /// **
   WildCardDepth,
+  /// **{1,3}, between min and max levels
+  WildCardDepthBounded(usize, Option<usize>),
   /// !
-  Not(Rc<Selector>),
-  Word(String)
+  Not(Rc<Selector>, Span),
+  Word(String),
+  /// / at the start of a route
+  Root,
+  /// ~ at the start of a route
+  Home,
+  /// (?i) at the start of an option
+  Flags(MatchOptions, Rc<Selector>),
+  /// / at the end of a route, only directories
+  Directory,
+  /// @name, with the selector it expands to
+  Alias(String, Rc<Selector>, Span)
 }
 
+type ParseResult<T> = Result<T, (SelectorErrorKind, Span)>;
+
 #[derive(Debug, Clone)]
 struct ParserLevel {
   option: Vec<Selector>,
   route: Vec<Selector>,
   concat: Vec<Selector>,
-  negate_next: bool
+  /// Where the pending negation mark is
+  negate_next: Option<Span>,
+  /// Where the group of this level starts
+  opened: Span,
+  /// Flags of the option being parsed
+  flags: Option<MatchOptions>,
+  /// The route being parsed ends with a slash so far
+  trailing_slash: bool,
+  /// Where the concatenation and the route being parsed start, and where the last of their items ends
+  concat_start: usize,
+  route_start: usize,
+  end: usize
 }
 
 impl ParserLevel {
-  fn new() -> Self {
+  fn new(opened: Span) -> Self {
     ParserLevel {
-      option: Vec::new(), route: Vec::new(), concat: Vec::new(), negate_next: false
+      option: Vec::new(), route: Vec::new(), concat: Vec::new(), negate_next: None, opened, flags: None,
+      trailing_slash: false, concat_start: 0, route_start: 0, end: 0
+    }
+  }
+
+  fn set_flags(&mut self, flags: MatchOptions, at: Span) -> ParseResult<()> {
+    if self.flags.is_some() || !self.route.is_empty() || !self.concat.is_empty() || self.negate_next.is_some() {
+      return Err((SelectorErrorKind::MisplacedFlags, at))
     }
+    self.flags = Some(flags);
+    Ok(())
   }
 
-  fn negate(&mut self) {
-    self.negate_next = true;
+  fn negate(&mut self, at: Span) {
+    self.negate_next = Some(at);
   }
 
-  fn push_to_concat(&mut self, atom: Selector) {
-    self.concat.push(if self.negate_next {
-      self.negate_next = false;
-      Selector::Not(Rc::new(atom))
+  /// `at` is where the atom is written, without its negation mark
+  fn push_to_concat(&mut self, atom: Selector, at: Span) {
+    self.trailing_slash = false;
+    let at = self.negate_next.as_ref().map_or(at.start, |n| n.start)..at.end;
+    if self.concat.is_empty() {
+      self.concat_start = at.start;
+      if self.route.is_empty() {
+        self.route_start = at.start;
+      }
+    }
+    self.end = at.end;
+    self.concat.push(if self.negate_next.take().is_some() {
+      Selector::Not(Rc::new(atom), at)
     } else {
       atom
     });
@@ -60,92 +153,191 @@ impl ParserLevel {
     Some(())
   }
 
-  fn push_to_route(&mut self, force: bool) -> Result<(), &'static str> {
-    if self.negate_next {
-      return Err("Unused negation mark")
+  fn push_to_route(&mut self, force: bool, at: &Span) -> ParseResult<()> {
+    if let Some(negation) = &self.negate_next {
+      return Err((SelectorErrorKind::UnusedNegation, negation.clone()))
+    }
+    if self.route.is_empty() {
+      // a leading slash or tilde anchors the route
+      if force && self.concat.is_empty() {
+        self.route.push(Selector::Root);
+        (self.route_start, self.end) = (at.start, at.end);
+        return Ok(())
+      }
+      if self.concat == [Selector::Word("~".to_string())] {
+        self.concat.clear();
+        self.route.push(Selector::Home);
+        return Ok(())
+      }
+    }
+    if !force && self.trailing_slash && self.concat.is_empty() {
+      self.trailing_slash = false;
+      self.route.push(Selector::Directory);
     }
+    let concat = self.concat_start..self.end;
     Self::base_push(&mut self.concat, &mut self.route,
-      force, |o| Selector::Concat(o.to_vec())
-    ).ok_or("Nothing before the slash")
+      force, |o| Selector::Concat(o.to_vec(), concat)
+    ).ok_or((SelectorErrorKind::NothingBeforeSlash, at.clone()))?;
+    self.trailing_slash = force;
+    if force {
+      self.end = at.end;
+    }
+    Ok(())
   }
 
-  fn push_to_option(&mut self, force: bool) -> Result<(), &'static str> {
-    self.push_to_route(false)?;
+  fn push_to_option(&mut self, force: bool, at: &Span) -> ParseResult<()> {
+    self.push_to_route(false, at)?;
+    let flags = self.flags.take();
+    if flags.is_some() && self.route.is_empty() {
+      return Err((SelectorErrorKind::Empty, at.clone()))
+    }
+    let route = self.route_start..self.end;
     Self::base_push(&mut self.route, &mut self.option,
-      force, |o| Selector::Route(o.to_vec())
-    ).ok_or("Nothing before the comma")
+      force, |o| Selector::Route(o.to_vec(), route)
+    ).ok_or((SelectorErrorKind::NothingBeforeComma, at.clone()))?;
+    if let Some(flags) = flags {
+      let option = self.option.pop().unwrap();
+      self.option.push(Selector::Flags(flags, Rc::new(option)));
+    }
+    Ok(())
   }
   
-  fn collect(&mut self) -> Result<Selector, &'static str> {
-    self.push_to_option(false)?;
+  /// `end` is where the group of this level is closed
+  fn collect(&mut self, end: usize) -> ParseResult<Selector> {
+    let at = self.opened.start..end;
+    self.push_to_option(false, &at)?;
     Ok(match &self.option[..] {
       [a] => a.clone(),
-      [] => return Err("Empty"),
+      [] => return Err((SelectorErrorKind::Empty, at)),
       o => Selector::Option(o.to_vec())
     })
   }
 }
 
-struct Parser {
+struct Parser<'a> {
   stack: Vec<ParserLevel>,
-  current: ParserLevel
+  current: ParserLevel,
+  aliases: &'a Aliases,
+  /// Aliases being expanded, to stop an alias from using itself
+  expanding: &'a [String]
 }
 
-impl Parser {
-  fn new() -> Self {
+impl<'a> Parser<'a> {
+  fn new(aliases: &'a Aliases, expanding: &'a [String]) -> Self {
     Parser {
       stack: Vec::new(),
-      current: ParserLevel::new()
+      current: ParserLevel::new(0..0),
+      aliases,
+      expanding
+    }
+  }
+
+  fn expand_alias(&self, name: &str) -> Result<Selector, SelectorErrorKind> {
+    use SelectorErrorKind::*;
+
+    if self.expanding.iter().any(|n| n == name) {
+      return Err(RecursiveAlias)
     }
+    let pattern = self.aliases.get(name).ok_or(UnknownAlias)?;
+    let mut expanding = self.expanding.to_vec();
+    expanding.push(name.to_string());
+    parse_tokens(pattern, self.aliases, &expanding).map_err(|(kind, _)| match kind {
+      UnknownAlias | RecursiveAlias => kind,
+      _ => InvalidAlias
+    })
   }
 
-  fn push_to_stack(&mut self) {
-    self.stack.push(self.current.clone());
-    self.current = ParserLevel::new();
+  fn push_to_stack(&mut self, at: Span) {
+    let level = std::mem::replace(&mut self.current, ParserLevel::new(at));
+    self.stack.push(level);
   }
 
-  fn pop_from_stack(&mut self) -> Result<(), &'static str> {
+  fn pop_from_stack(&mut self, at: Span) -> ParseResult<()> {
     if let Some(mut top) = self.stack.pop() {
-      self.current.push_to_option(false)?;
-      top.push_to_concat(self.current.collect()?);
+      self.current.push_to_option(false, &at)?;
+      let group = self.current.opened.start..at.end;
+      top.push_to_concat(self.current.collect(at.end)?, group);
       self.current = top;
       return Ok(())
     }
-    Err("No opened group")
+    Err((SelectorErrorKind::NoOpenedGroup, at))
   }
 
-  fn append_token(&mut self, tk: Token) -> Result<(), &'static str> {
-    Ok(match tk {
-        Token::WildCard => self.current.push_to_concat(Selector::WildCard),
-        Token::WildCardDepth => self.current.push_to_concat(Selector::WildCardDepth),
-        Token::Not => self.current.negate(),
-        Token::Word(n) => self.current.push_to_concat(Selector::Word(n.clone())),
-        Token::Open => self.push_to_stack(),
-        Token::Close => self.pop_from_stack()?,
-        Token::Comma => self.current.push_to_option(true)?,
-        Token::Slash => self.current.push_to_route(true)?,
-    })
+  fn append_token(&mut self, tk: Token, at: Span) -> ParseResult<()> {
+    match tk {
+        Token::WildCard => self.current.push_to_concat(Selector::WildCard, at),
+        Token::WildCardDepth => self.current.push_to_concat(Selector::WildCardDepth, at),
+        Token::WildCardDepthBounded(min, max) => {
+          if max.is_some_and(|max| max < min) {
+            return Err((SelectorErrorKind::InvalidDepth, at))
+          }
+          if max.unwrap_or(min) > MAX_DEPTH {
+            return Err((SelectorErrorKind::DepthTooLarge, at))
+          }
+          self.current.push_to_concat(Selector::WildCardDepthBounded(min, max), at)
+        },
+        Token::Not => self.current.negate(at),
+        Token::Word(n) => self.current.push_to_concat(Selector::Word(n), at),
+        Token::Open => self.push_to_stack(at),
+        Token::Close => self.pop_from_stack(at)?,
+        Token::Comma => self.current.push_to_option(true, &at)?,
+        Token::Slash => self.current.push_to_route(true, &at)?,
+        Token::Range(from, to, width) => {
+          if from.abs_diff(to) >= MAX_RANGE {
+            return Err((SelectorErrorKind::RangeTooLarge, at))
+          }
+          let numbers: Vec<_> = if from <= to { (from..=to).collect() } else { (to..=from).rev().collect() };
+          self.current.push_to_concat(Selector::Option(
+            numbers.into_iter().map(|n| Selector::Word(format!("{:0width$}", n, width = width))).collect()
+          ), at)
+        },
+        Token::Alias(name) => {
+          let selector = self.expand_alias(&name).map_err(|kind| (kind, at.clone()))?;
+          self.current.push_to_concat(Selector::Alias(name, Rc::new(selector), at.clone()), at)
+        },
+        Token::Flags(f) => {
+          let flags = MatchOptions::parse(&f).ok_or((SelectorErrorKind::InvalidFlags, at.clone()))?;
+          self.current.set_flags(flags, at)?
+        },
+    }
+    Ok(())
   }
 
-  fn get_valid_selector(&mut self) -> Result<Selector, &'static str> {
+  fn get_valid_selector(&mut self, end: usize) -> ParseResult<Selector> {
     if !self.stack.is_empty() {
-      return Err("Unclosed group");
+      return Err((SelectorErrorKind::UnclosedGroup, self.current.opened.clone()));
     }
     // if stack is empty we are in the first level where no group is open
-    self.current.collect()
+    self.current.collect(end)
   }
 }
 
-pub fn parse_selector<'a>(pattern: &'a str) -> Result<Selector, &'static str> {
-  let mut parser = Parser::new();
-  for token in Token::many_from(pattern.chars()).ok_or("Invalid pattern")? {
-    parser.append_token(token)?;
+fn parse_tokens(pattern: &str, aliases: &Aliases, expanding: &[String]) -> ParseResult<Selector> {
+  let mut parser = Parser::new(aliases, expanding);
+  for (token, span) in Token::many_from(pattern).ok_or((SelectorErrorKind::Empty, 0..0))? {
+    parser.append_token(token, span)?;
   }
-  parser.get_valid_selector()
+  parser.get_valid_selector(pattern.len())
+}
+
+/// Parses a selector once `${VAR}` references are replaced, `@name` uses the given aliases.
+/// The selector is also expanded once, so what can not be watched fails here with its position
+pub fn parse_selector_with(pattern: &str, aliases: &Aliases) -> Result<Selector, SelectorError> {
+  let pattern = interpolate(pattern)?;
+  parse_tokens(&pattern, aliases, &[])
+    .and_then(|selector| walk::check(&selector).map(|_| selector))
+    .map_err(|(kind, span)| SelectorError { kind, span, pattern })
+}
+
+/// Parses a selector with only the preset aliases
+#[cfg(test)]
+pub fn parse_selector(pattern: &str) -> Result<Selector, SelectorError> {
+  parse_selector_with(pattern, &Aliases::default())
 }
 
 #[cfg(test)]
 mod tests {
+  use crate::parsing::interpolation::interpolate_with;
   use super::*;
   use Selector::*;
 
@@ -156,30 +348,46 @@ mod tests {
   }
   macro_rules! n {
     ($e:expr) => {
-      Not(Rc::new($e))
+      Not(Rc::new($e), 0..0)
     };
   }
   macro_rules! make {
     [rt $($e:expr),+] => {
-      Route(vec![$($e),+])
+      Route(vec![$($e),+], 0..0)
     };
     [cc $($e:expr),+] => {
-      Concat(vec![$($e),+])
+      Concat(vec![$($e),+], 0..0)
     };
     [op $($e:expr),+] => {
       Option(vec![$($e),+])
     };
   }
 
+  /// The selector with every span at 0, to compare trees
+  fn unspan(selector: &Selector) -> Selector {
+    let all = |v: &[Selector]| v.iter().map(unspan).collect();
+    match selector {
+      Option(v) => Option(all(v)),
+      Route(v, _) => Route(all(v), 0..0),
+      Concat(v, _) => Concat(all(v), 0..0),
+      Not(s, _) => Not(Rc::new(unspan(s)), 0..0),
+      Flags(options, s) => Flags(*options, Rc::new(unspan(s))),
+      Alias(name, s, _) => Alias(name.clone(), Rc::new(unspan(s)), 0..0),
+      other => other.clone()
+    }
+  }
+
   #[test]
   fn test_selector() {
+    let parse_selector = |pattern: &str| parse_selector(pattern).map(|s| unspan(&s));
+    let parse_selector_with = |pattern: &str, aliases: &Aliases| parse_selector_with(pattern, aliases).map(|s| unspan(&s));
     assert_eq!(parse_selector("word.rs"), Ok(w!("word.rs")));
     assert_eq!(parse_selector("word.rs/*"), Ok(make![rt w!("word.rs"), WildCard]));
     assert_eq!(parse_selector("**/*"), Ok(make![rt WildCardDepth, WildCard]));
-    assert!(matches!(parse_selector("**//*"), Err(_)));
-    assert!(matches!(parse_selector("{,a}"), Err(_)));
-    assert!(matches!(parse_selector(""), Err(_)));
-    assert!(matches!(parse_selector("{}"), Err(_)));
+    assert!(parse_selector("**//*").is_err());
+    assert!(parse_selector("{,a}").is_err());
+    assert!(parse_selector("").is_err());
+    assert!(parse_selector("{}").is_err());
     assert_eq!(parse_selector("{ab,cd}/c"), Ok(make![rt make![op w!("ab"), w!("cd")], w!("c")]));
     assert_eq!(parse_selector("{a,b}"), Ok(make![op w!("a"), w!("b")]));
     assert_eq!(parse_selector("a,b/c"), Ok(make![op w!("a"), make![rt w!("b"), w!("c")]]));
@@ -198,10 +406,95 @@ mod tests {
     assert_eq!(parse_selector("a/b/{c,d}"), Ok(
       make![rt w!("a"), w!("b"), make![op w!("c"), w!("d")]]
     ));
-    assert!(matches!(parse_selector("a/!b/!{c,d}!"), Err(_)));
+    assert!(parse_selector("a/!b/!{c,d}!").is_err());
     assert_eq!(parse_selector("a/!b/!{c,d}"), Ok(
       make![rt w!("a"), n!(w!("b")), n!(make![op w!("c"), w!("d")])]
     ));
-    assert_eq!(parse_selector("!{a,*c}"), Ok(n!(make![op w!("a"), make![cc WildCard, w!("c")]])));
+    assert_eq!(parse_selector("!{a,b}"), Ok(n!(make![op w!("a"), w!("b")])));
+    assert_eq!(parse_selector("/etc/app/*.conf"), Ok(
+      make![rt Root, w!("etc"), w!("app"), make![cc WildCard, w!(".conf")]]
+    ));
+    assert_eq!(parse_selector("/"), Ok(Root));
+    assert_eq!(parse_selector("~"), Ok(Home));
+    assert_eq!(parse_selector("~/.config/**,src/~"), Ok(make![op
+      make![rt Home, w!(".config"), WildCardDepth], make![rt w!("src"), w!("~")]
+    ]));
+    assert_eq!(parse_selector("{/a,b}"), Ok(make![op make![rt Root, w!("a")], w!("b")]));
+    assert!(parse_selector("a//b").is_err());
+    let flags = |ignore_case, hidden| MatchOptions { ignore_case, hidden };
+    assert_eq!(parse_selector("(?i)a/b,{c,(?h)*}"), Ok(make![op
+      Flags(flags(true, false), Rc::new(make![rt w!("a"), w!("b")])),
+      make![op w!("c"), Flags(flags(false, true), Rc::new(WildCard))]
+    ]));
+    assert_eq!(parse_selector("(?hi)/etc"), Ok(Flags(flags(true, true), Rc::new(make![rt Root, w!("etc")]))));
+    assert_eq!(parse_selector("a/**{,2}/b"), Ok(make![rt w!("a"), WildCardDepthBounded(0, Some(2)), w!("b")]));
+    assert_eq!(parse_selector("{Cargo.toml,*.rs}/"), Ok(
+      make![rt make![op w!("Cargo.toml"), make![cc WildCard, w!(".rs")]], Directory]
+    ));
+    assert_eq!(parse_selector("src/*/,a/{b/}"), Ok(make![op
+      make![rt w!("src"), WildCard, Directory], make![rt w!("a"), make![rt w!("b"), Directory]]
+    ]));
+    assert_eq!(parse_selector("/"), Ok(Root));
+    assert_eq!(parse_selector("a/b"), Ok(make![rt w!("a"), w!("b")]));
+    assert_eq!(parse_selector("shard-{1..3}"), Ok(make![cc w!("shard-"), make![op w!("1"), w!("2"), w!("3")]]));
+    assert_eq!(parse_selector("{3..1}/{-1..1}/{09..10}"), Ok(make![rt
+      make![op w!("3"), w!("2"), w!("1")], make![op w!("-1"), w!("0"), w!("1")], make![op w!("09"), w!("10")]
+    ]));
+    assert_eq!(parse_selector("@go"), Ok(Alias("go".to_string(), Rc::new(make![op
+      make![rt WildCardDepth, make![cc WildCard, w!(".go")]], w!("go.mod"), w!("go.sum")
+    ]), 0..0)));
+    let mut aliases = Aliases::default();
+    aliases.define("web", "{static,@go}");
+    aliases.define("go", "*.go");
+    assert_eq!(parse_selector_with("x/@web", &aliases), Ok(make![rt w!("x"), Alias("web".to_string(), Rc::new(make![op
+      w!("static"), Alias("go".to_string(), Rc::new(make![cc WildCard, w!(".go")]), 0..0)
+    ]), 0..0)]));
+    let pattern = interpolate_with("${SRC}/*", |name| (name == "SRC").then(|| "src".to_string())).unwrap();
+    assert_eq!(parse_selector(&pattern), Ok(make![rt w!("src"), WildCard]));
+  }
+
+  #[test]
+  fn test_selector_errors() {
+    use SelectorErrorKind::*;
+
+    let error = |pattern: &str| parse_selector(pattern).map_err(|e| (e.kind, e.span)).unwrap_err();
+    assert_eq!(error(""), (Empty, 0..0));
+    assert_eq!(error("{}"), (Empty, 0..2));
+    assert_eq!(error("a/{{}}"), (Empty, 3..5));
+    assert_eq!(error("**//*"), (NothingBeforeSlash, 3..4));
+    assert_eq!(error("{a,,b}"), (NothingBeforeComma, 3..4));
+    assert_eq!(error("a/!b/!{c,d}!"), (UnusedNegation, 11..12));
+    assert_eq!(error("{a,!}"), (UnusedNegation, 3..4));
+    assert_eq!(error("a}"), (NoOpenedGroup, 1..2));
+    assert_eq!(error("x{a,{b}"), (UnclosedGroup, 1..2));
+    assert_eq!(error("(?x)a"), (InvalidFlags, 0..4));
+    assert_eq!(error("!(?i)a"), (MisplacedFlags, 1..5));
+    assert_eq!(error("(?i)(?h)a"), (MisplacedFlags, 4..8));
+    assert_eq!(error("a/(?i)b"), (MisplacedFlags, 2..6));
+    assert_eq!(error("(?i)"), (Empty, 0..4));
+    assert_eq!(error("(?i"), (InvalidFlags, 0..3));
+    assert_eq!(error("a/**{3,1}"), (InvalidDepth, 2..9));
+    assert_eq!(error("{1..100000}"), (RangeTooLarge, 0..11));
+    assert_eq!(error("a/**{,3000}"), (DepthTooLarge, 2..11));
+    assert_eq!(error("**{65,}"), (DepthTooLarge, 0..7));
+    assert!(parse_selector("**{64,}").is_ok() && parse_selector("**{,64}").is_ok());
+    assert_eq!(error("${REBURN_TEST_UNSET}/a"), (UnsetVariable, 0..20));
+    assert_eq!(error("a/@nope"), (UnknownAlias, 2..7));
+    assert_eq!(error("src/**/!*_gen.rs"), (InvalidNegation, 7..9));
+    assert_eq!(error("a/!{b,*c}/d"), (InvalidNegation, 2..9));
+    assert_eq!(error("a/{/b}"), (MisplacedAnchor, 0..6));
+    assert_eq!(error("x,{a/}/b"), (MisplacedDirectory, 2..8));
+    assert_eq!(error("x{a,**}"), (InvalidJoin, 0..7));
+    let mut aliases = Aliases::default();
+    aliases.define("a", "x/@b");
+    aliases.define("b", "{@a}");
+    aliases.define("c", "x//y");
+    aliases.define("d", "@c,@e");
+    aliases.define("e", "!*");
+    let error = |pattern: &str| parse_selector_with(pattern, &aliases).map_err(|e| (e.kind, e.span)).unwrap_err();
+    assert_eq!(error("src,@b"), (RecursiveAlias, 4..6));
+    assert_eq!(error("@c"), (InvalidAlias, 0..2));
+    assert_eq!(error("@d"), (InvalidAlias, 0..2));
+    assert_eq!(error("a/@e"), (InvalidAlias, 2..4));
   }
 }
\ No newline at end of file
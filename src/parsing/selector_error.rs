@@ -0,0 +1,125 @@
+use std::fmt;
+
+use super::selector_tokens::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SelectorErrorKind {
+  Empty,
+  UnusedNegation,
+  NothingBeforeSlash,
+  NothingBeforeComma,
+  NoOpenedGroup,
+  UnclosedGroup,
+  InvalidFlags,
+  MisplacedFlags,
+  InvalidDepth,
+  RangeTooLarge,
+  DepthTooLarge,
+  UnsetVariable,
+  UnclosedVariable,
+  InvalidVariable,
+  UnknownAlias,
+  RecursiveAlias,
+  InvalidAlias,
+  InvalidNegation,
+  InvalidJoin,
+  MisplacedAnchor,
+  MisplacedDirectory
+}
+
+impl SelectorErrorKind {
+  pub fn message(&self) -> &'static str {
+    match self {
+      Self::Empty => "Empty",
+      Self::UnusedNegation => "Unused negation mark",
+      Self::NothingBeforeSlash => "Nothing before the slash",
+      Self::NothingBeforeComma => "Nothing before the comma",
+      Self::NoOpenedGroup => "No opened group",
+      Self::UnclosedGroup => "Unclosed group",
+      Self::InvalidFlags => "Invalid flags",
+      Self::MisplacedFlags => "Misplaced flags",
+      Self::InvalidDepth => "Invalid depth range",
+      Self::RangeTooLarge => "Range too large",
+      Self::DepthTooLarge => "Depth too large",
+      Self::UnsetVariable => "Environment variable not set",
+      Self::UnclosedVariable => "Unclosed variable",
+      Self::InvalidVariable => "Invalid variable name",
+      Self::UnknownAlias => "Unknown alias",
+      Self::RecursiveAlias => "Alias used inside itself",
+      Self::InvalidAlias => "Invalid selector in alias",
+      Self::InvalidNegation => "Only a literal can be negated",
+      Self::InvalidJoin => "Group can not be joined to a name",
+      Self::MisplacedAnchor => "Only the start of a route can be absolute",
+      Self::MisplacedDirectory => "A trailing slash must end the route",
+    }
+  }
+
+  pub fn hint(&self) -> &'static str {
+    match self {
+      Self::Empty => "write at least one name or wildcard",
+      Self::UnusedNegation => "`!` must be followed by the name or group it negates",
+      Self::NothingBeforeSlash => "remove the repeated slash",
+      Self::NothingBeforeComma => "every option needs a pattern, remove the extra comma",
+      Self::NoOpenedGroup => "remove the `}` or open the group with `{`",
+      Self::UnclosedGroup => "close the group with `}`",
+      Self::InvalidFlags => "flags are written as `(?i)`, `(?h)` or `(?ih)`",
+      Self::MisplacedFlags => "flags go at the start of the selector or of an option",
+      Self::InvalidDepth => "the minimum depth can not be greater than the maximum, like `**{1,3}`",
+      Self::RangeTooLarge => "ranges expand to at most 10000 options",
+      Self::DepthTooLarge => "a bounded `**` reaches at most 64 levels, `**` alone has no limit",
+      Self::UnsetVariable => "set the variable or give a default with `${VAR:-default}`",
+      Self::UnclosedVariable => "close the variable with `}`",
+      Self::InvalidVariable => "variable names use letters, digits and `_`, like `${OUT_DIR}`",
+      Self::UnknownAlias => "use a preset like `@rust`, `@node`, `@python` or `@go`, or define it in the config file",
+      Self::RecursiveAlias => "an alias can not expand to itself, directly or through other aliases",
+      Self::InvalidAlias => "check the alias with `reburn explain`",
+      Self::InvalidNegation => "`!` negates the literal after it inside a name, like `*!_gen.rs` for names without `_gen`",
+      Self::InvalidJoin => "options joined to a name can not start or end with `**`, `/`, `~` or flags",
+      Self::MisplacedAnchor => "`/` and `~` anchor a route only at its start, an option can not be absolute in the middle of a route",
+      Self::MisplacedDirectory => "remove the slash, or move it to the end of the route",
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectorError {
+  pub kind: SelectorErrorKind,
+  /// Byte range of `pattern` where the error was found
+  pub span: Span,
+  pub pattern: String
+}
+
+impl SelectorError {
+  pub fn hint(&self) -> &'static str {
+    self.kind.hint()
+  }
+
+  /// The pattern with a caret under the bad position
+  pub fn render(&self) -> String {
+    let column = self.pattern[..self.span.start].chars().count();
+    let width = self.pattern[self.span.clone()].chars().count().max(1);
+    format!(
+      "{}\n  {}\n  {}{}\nhint: {}",
+      self, self.pattern, " ".repeat(column), "^".repeat(width), self.hint()
+    )
+  }
+}
+
+impl fmt::Display for SelectorError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Invalid selector: {}", self.kind.message())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_render() {
+    let error = SelectorError {
+      kind: SelectorErrorKind::NothingBeforeComma, span: 5..6, pattern: "{añ,,b}".to_string()
+    };
+    assert_eq!(error.render(), "Invalid selector: Nothing before the comma\n  {añ,,b}\n      ^\nhint: every option needs a pattern, remove the extra comma");
+  }
+}
@@ -1,4 +1,7 @@
-use std::str::Chars;
+use std::ops::Range;
+
+/// Byte range of a token in the pattern
+pub type Span = Range<usize>;
 
 #[derive(Debug, PartialEq, Clone)]
 pub(super) enum Token {
@@ -6,6 +9,8 @@ pub(super) enum Token {
   WildCard,
   /// **
   WildCardDepth,
+  /// **{1,3}, **{,3} or **{1,}
+  WildCardDepthBounded(usize, Option<usize>),
   Word(String),
   /// {
   Open,
@@ -16,12 +21,20 @@ pub(super) enum Token {
   /// /
   Slash,
   /// !
-  Not
+  Not,
+  /// (?i), kept as written
+  Flags(String),
+  /// {1..16}, with the width of zero padded bounds
+  Range(i64, i64, usize),
+  /// @name
+  Alias(String)
 }
 
 impl Token {
   fn next(c: char, tk: &Option<Token>) -> (Option<Token>, Token) {
     match (c, tk) {
+      (_, Some(Self::Flags(s))) if !s.ends_with(')') => (None, Self::Flags(format!("{}{}", s, c))),
+      ('?', Some(Self::Word(s))) if s == "(" => (None, Self::Flags("(?".to_string())),
       ('/', _) => (tk.clone(), Self::Slash),
       ('{', _) => (tk.clone(), Self::Open),
       ('}', _) => (tk.clone(), Self::Close),
@@ -29,28 +42,91 @@ impl Token {
       ('*', Some(Self::WildCard)) => (None, Self::WildCardDepth),
       ('*', _) => (tk.clone(), Self::WildCard),
       ('!', _) => (tk.clone(), Self::Not),
+      (_, Some(Self::Alias(s))) if c.is_ascii_alphanumeric() || c == '_' || c == '-' =>
+        (None, Self::Alias(format!("{}{}", s, c))),
       (_, Some(Self::Word(s))) => (None, Self::Word(format!("{}{}", s, c))),
+      // inside a word `@` is just a character
+      ('@', _) => (tk.clone(), Self::Alias(String::new())),
       (_, _) => (tk.clone(), Self::Word(c.to_string()))
     }
   }
 
-  pub(super) fn many_from(iter: Chars) -> Option<Vec<Token>> {
+  pub(super) fn many_from(pattern: &str) -> Option<Vec<(Token, Span)>> {
     let mut v = Vec::new();
     let mut tk = None;
-    for c in iter {
+    let mut start = 0;
+    for (i, c) in pattern.char_indices() {
       let (push, next) = Self::next(c, &tk);
+      // the character may extend the previous token instead of starting a new one
+      let extends = push.is_none() && tk.is_some();
       if let Some(push) = push {
-        v.push(push)
+        v.push((push, start..i));
+      }
+      if !extends {
+        start = i;
       }
       tk = Some(next)
     }
     if let Some(tk) = tk {
-      v.push(tk);
+      v.push((tk, start..pattern.len()));
     }
     if v.is_empty() {
       return None
     }
-    Some(v)
+    Some(Self::merge_groups(v))
+  }
+
+  /// A `{from..to}` group, with the size of the group
+  fn range_at(tokens: &[(Token, Span)]) -> Option<(Token, usize)> {
+    use Token::*;
+
+    let kinds: Vec<&Token> = tokens.iter().take(3).map(|(t, _)| t).collect();
+    let [Open, Word(w), Close] = kinds[..] else {
+      return None
+    };
+    let (from, to) = w.split_once("..")?;
+    let number = |n: &str| n.strip_prefix('-').unwrap_or(n).chars().all(|c| c.is_ascii_digit())
+      .then(|| n.parse::<i64>().ok()).flatten();
+    let padded = |n: &str| n.strip_prefix('-').unwrap_or(n).len() > 1 && n.trim_start_matches('-').starts_with('0');
+    let width = if padded(from) || padded(to) { from.len().max(to.len()) } else { 0 };
+    Some((Range(number(from)?, number(to)?, width), 3))
+  }
+
+  /// A ** followed by a `{min,max}` group, with the size of the group
+  fn bounded_at(tokens: &[(Token, Span)]) -> Option<(Token, usize)> {
+    use Token::*;
+
+    let number = |t: &Token| match t {
+      Word(w) if w.chars().all(|c| c.is_ascii_digit()) => w.parse::<usize>().ok(),
+      _ => None
+    };
+    let kinds: Vec<&Token> = tokens.iter().take(6).map(|(t, _)| t).collect();
+    match kinds[..] {
+      [WildCardDepth, Open, Comma, n, Close, ..] => Some((WildCardDepthBounded(0, Some(number(n)?)), 5)),
+      [WildCardDepth, Open, m, Comma, Close, ..] => Some((WildCardDepthBounded(number(m)?, None), 5)),
+      [WildCardDepth, Open, m, Comma, n, Close, ..] =>
+        Some((WildCardDepthBounded(number(m)?, Some(number(n)?)), 6)),
+      _ => None
+    }
+  }
+
+  /// Groups with a special meaning are turned into a single token
+  fn merge_groups(tokens: Vec<(Token, Span)>) -> Vec<(Token, Span)> {
+    let mut v = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+      match Self::bounded_at(&tokens[i..]).or_else(|| Self::range_at(&tokens[i..])) {
+        Some((tk, len)) => {
+          v.push((tk, tokens[i].1.start..tokens[i + len - 1].1.end));
+          i += len;
+        },
+        None => {
+          v.push(tokens[i].clone());
+          i += 1;
+        }
+      }
+    }
+    v
   }
 }
 
@@ -61,10 +137,10 @@ mod tests {
 
   macro_rules! assert_tk {
     ($str: expr, None) => {
-      assert_eq!(Token::many_from($str.chars()), None);
+      assert_eq!(Token::many_from($str), None);
     };
     ($str: expr, $($e: expr),+) => {
-      assert_eq!(Token::many_from($str.chars()), Some(vec![$($e,)+]))
+      assert_eq!(Token::many_from($str).map(|v| v.into_iter().map(|(t, _)| t).collect()), Some(vec![$($e,)+]))
     };
   }
 
@@ -83,5 +159,24 @@ mod tests {
       Open, Word("Cargo.toml".to_string()), Comma, WildCard, Word(".rs".to_string()), Close, Slash);
     assert_tk!("!test!{c,!d}",
       Not, Word("test".to_string()), Not, Open, Word("c".to_string()), Comma, Not, Word("d".to_string()), Close);
+    assert_tk!("**{,2}/**{1,3}/**{2,}/**{a,b}",
+      WildCardDepthBounded(0, Some(2)), Slash, WildCardDepthBounded(1, Some(3)), Slash, WildCardDepthBounded(2, None), Slash,
+      WildCardDepth, Open, Word("a".to_string()), Comma, Word("b".to_string()), Close);
+    assert_tk!("shard-{1..16}/{08..10}{-2..x}",
+      Word("shard-".to_string()), Range(1, 16, 0), Slash, Range(8, 10, 2),
+      Open, Word("-2..x".to_string()), Close);
+    assert_tk!("{@rust,a@b}/@x.y",
+      Open, Alias("rust".to_string()), Comma, Word("a@b".to_string()), Close, Slash,
+      Alias("x".to_string()), Word(".y".to_string()));
+    assert_tk!("(?ih)a,(1)/(?",
+      Flags("(?ih)".to_string()), Word("a".to_string()), Comma, Word("(1)".to_string()), Slash, Flags("(?".to_string()));
+  }
+
+  #[test]
+  fn test_spans() {
+    let spans: Vec<_> = Token::many_from("**/ñu{a,*}").unwrap().into_iter().map(|(_, s)| s).collect();
+    assert_eq!(spans, vec![0..2, 2..3, 3..6, 6..7, 7..8, 8..9, 9..10, 10..11]);
+    let spans: Vec<_> = Token::many_from("a/**{1,3}/").unwrap().into_iter().map(|(_, s)| s).collect();
+    assert_eq!(spans, vec![0..1, 1..2, 2..9, 9..10]);
   }
 }
\ No newline at end of file
@@ -1,3 +1,9 @@
+use ::std::{process::Command, ffi::OsStr, path::Path};
+
+mod resolve;
+
+pub use resolve::resolve;
+
 #[cfg(windows)]
 mod windows;
 
@@ -8,4 +14,14 @@ pub use windows::*;
 mod std;
 
 #[cfg(not(windows))]
-pub use std::*;
\ No newline at end of file
+pub use std::*;
+
+/// The command for `args` with `env` added to its environment, running in `dir` if given
+pub fn command<S : AsRef<OsStr>>(args: &[S], env: &[(String, String)], dir: Option<&Path>) -> Command {
+	let mut command = Command::new(&args[0]);
+	command.args(&args[1..]).envs(env.iter().cloned());
+	if let Some(dir) = dir {
+		command.current_dir(dir);
+	}
+	command
+}
@@ -0,0 +1,154 @@
+use std::{env, fs, path::{Path, PathBuf, MAIN_SEPARATOR}};
+
+/// Most suggestions given for a command that is not found
+const MAX_SUGGESTIONS: usize = 3;
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+	use std::os::unix::fs::PermissionsExt;
+	fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+	path.is_file()
+}
+
+/// The names `program` can have on disk, Windows also tries the extensions of `PATHEXT`
+fn file_names(program: &str) -> Vec<String> {
+	let mut names = vec![program.to_string()];
+	if cfg!(windows) && Path::new(program).extension().is_none() {
+		let extensions = env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+		names.extend(extensions.split(';').filter(|e| !e.is_empty()).map(|e| format!("{}{}", program, e.to_lowercase())));
+	}
+	names
+}
+
+/// Edits between two names, swapping two neighbour letters is one edit
+fn distance(a: &str, b: &str) -> usize {
+	let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+	let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+	for (i, row) in rows.iter_mut().enumerate() {
+		row[0] = i;
+	}
+	rows[0] = (0..=b.len()).collect();
+	for i in 1..=a.len() {
+		for j in 1..=b.len() {
+			let cost = usize::from(a[i - 1] != b[j - 1]);
+			rows[i][j] = (rows[i - 1][j] + 1).min(rows[i][j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+			if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+				rows[i][j] = rows[i][j].min(rows[i - 2][j - 2] + 1);
+			}
+		}
+	}
+	rows[a.len()][b.len()]
+}
+
+/// The closest names to `program` among `names`, nearest first
+fn closest<I: IntoIterator<Item = String>>(program: &str, names: I) -> Vec<String> {
+	let limit = program.chars().count() / 4 + 1;
+	let mut near: Vec<_> = names.into_iter()
+		.map(|name| (distance(program, &name), name))
+		.filter(|(d, _)| *d <= limit)
+		.collect();
+	near.sort();
+	near.dedup_by(|a, b| a.1 == b.1);
+	near.into_iter().take(MAX_SUGGESTIONS).map(|(_, name)| name).collect()
+}
+
+/// Names of the executables on `PATH`, without their Windows extension
+fn path_commands() -> Vec<String> {
+	env::var_os("PATH").iter().flat_map(env::split_paths).filter_map(|dir| fs::read_dir(dir).ok()).flatten().flatten()
+		.filter(|entry| is_executable(&entry.path()))
+		.filter_map(|entry| entry.file_name().into_string().ok())
+		.map(|name| if cfg!(windows) { Path::new(&name).file_stem().and_then(|s| s.to_str()).unwrap_or(&name).to_string() } else { name })
+		.collect()
+}
+
+/// The first executable named `program` on `PATH`, and any file with the name that is not executable
+fn search_path(program: &str) -> (Option<PathBuf>, Option<PathBuf>) {
+	let mut not_executable = None;
+	for dir in env::var_os("PATH").iter().flat_map(env::split_paths) {
+		for name in file_names(program) {
+			let path = dir.join(name);
+			if is_executable(&path) {
+				return (Some(path), not_executable)
+			}
+			if path.is_file() {
+				not_executable.get_or_insert(path);
+			}
+		}
+	}
+	(None, not_executable)
+}
+
+fn did_you_mean(suggestions: &[String]) -> String {
+	match suggestions {
+		[] => String::new(),
+		[one] => format!(", did you mean `{}`?", one),
+		[init @ .., last] => format!(
+			", did you mean {} or `{}`?", init.iter().map(|s| format!("`{}`", s)).collect::<Vec<_>>().join(", "), last
+		)
+	}
+}
+
+/// The file `program` runs, looked up on `PATH` when it has no directory and from `dir` otherwise.
+/// The error names the command and suggests close names when it is not found
+pub fn resolve(program: &str, dir: Option<&Path>) -> Result<PathBuf, String> {
+	if program.contains('/') || program.contains(MAIN_SEPARATOR) {
+		let path = dir.map(|d| d.join(program)).unwrap_or_else(|| PathBuf::from(program));
+		return match file_names(program).iter().map(|n| dir.map(|d| d.join(n)).unwrap_or_else(|| n.into())).find(|p| p.is_file()) {
+			Some(path) if is_executable(&path) => Ok(path),
+			Some(_) => Err(format!("`{}` is not executable, `chmod +x {}` allows it", program, program)),
+			None => {
+				let name = path.file_name().and_then(|n| n.to_str()).unwrap_or(program);
+				let hint = match search_path(name) {
+					(Some(found), _) => format!(", `{}` is at {}", name, found.display()),
+					_ => did_you_mean(&closest(name, path_commands()))
+				};
+				Err(format!("`{}` does not exist{}", program, hint))
+			}
+		}
+	}
+	match search_path(program) {
+		(Some(path), _) => Ok(path),
+		(None, Some(path)) => Err(format!("`{}` is not executable, found at {}", program, path.display())),
+		(None, None) => Err(format!("Command `{}` not found in PATH{}", program, did_you_mean(&closest(program, path_commands()))))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::testing::TempDir;
+	use super::*;
+
+	#[test]
+	fn test_distance() {
+		assert_eq!(distance("pyhton", "python"), 1);
+		assert_eq!(distance("pyhton", "python3"), 2);
+		assert_eq!(distance("", "sh"), 2);
+		assert_eq!(distance("node", "node"), 0);
+		assert_eq!(distance("cargo", "go"), 3);
+	}
+
+	#[test]
+	fn test_closest() {
+		let names = ["python3", "python", "perl", "pydoc", "python3"].map(String::from);
+		assert_eq!(closest("pyhton", names.clone()), ["python", "python3"]);
+		assert_eq!(closest("xyz", names), Vec::<String>::new());
+		assert_eq!(did_you_mean(&["a".to_string(), "b".to_string(), "c".to_string()]), ", did you mean `a`, `b` or `c`?");
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_resolve() {
+		assert_eq!(resolve("sh", None).map(|p| p.file_name().unwrap().to_owned()), Ok("sh".into()));
+		assert!(resolve("/bin/sh", None).is_ok());
+		assert!(resolve("reburn-no-such-command", None).unwrap_err().starts_with("Command `reburn-no-such-command` not found in PATH"));
+		assert_eq!(resolve("/reburn/no/sh", None).unwrap_err().split_once(", `sh` is at ").map(|(e, _)| e), Some("`/reburn/no/sh` does not exist"));
+		let dir = TempDir::new("resolve");
+		fs::write(dir.join("script"), "").unwrap();
+		let relative = "./script";
+		assert_eq!(resolve(relative, Some(&dir)), Err(format!("`{}` is not executable, `chmod +x {}` allows it", relative, relative)));
+	}
+}
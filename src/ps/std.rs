@@ -1,13 +1,147 @@
-use std::{process::{Command, Child}, ffi::OsStr, io};
+use std::{process::{Child, Command}, ffi::OsStr, io, path::Path, fs};
+
+use super::command;
+
+/// Every process with its parent, from `/proc` or else from `ps`
+fn process_parents() -> Vec<(u32, u32)> {
+	if let Ok(entries) = fs::read_dir("/proc") {
+		// the name in the stat line is in parentheses and may have spaces, the parent is the second field after it
+		return entries.flatten().filter_map(|entry| {
+			let pid = entry.file_name().to_str()?.parse().ok()?;
+			let stat = fs::read_to_string(entry.path().join("stat")).ok()?;
+			let ppid = stat.rsplit_once(')')?.1.split_whitespace().nth(1)?.parse().ok()?;
+			Some((pid, ppid))
+		}).collect()
+	}
+	let Ok(output) = Command::new("ps").args(["-A", "-o", "pid=", "-o", "ppid="]).output() else {
+		return Vec::new()
+	};
+	String::from_utf8_lossy(&output.stdout).lines().filter_map(|line| {
+		let mut ids = line.split_whitespace().map(|id| id.parse().ok());
+		Some((ids.next()??, ids.next()??))
+	}).collect()
+}
+
+/// `root` and the processes it started, like the members of a shell pipeline, and the ones they started
+fn process_tree(root: u32, parents: &[(u32, u32)]) -> Vec<u32> {
+	let mut tree = vec![root];
+	let mut i = 0;
+	while i < tree.len() {
+		let parent = tree[i];
+		tree.extend(parents.iter().filter(|(pid, ppid)| *ppid == parent && !tree.contains(pid)).map(|(pid, _)| *pid).collect::<Vec<_>>());
+		i += 1;
+	}
+	tree
+}
 
 pub struct Process(Child);
 
 impl Process {
-	pub fn run<S : AsRef<OsStr>>(args: &[S]) -> io::Result<Self> {
-		Ok(Process(Command::new(&args[0]).args(&args[1..]).spawn()?))
+	/// `env` is added to the environment of the child, which runs in `dir` if given.
+	/// The child stays in the process group of the terminal so it can read from it
+	pub fn run<S : AsRef<OsStr>>(args: &[S], env: &[(String, String)], dir: Option<&Path>) -> io::Result<Self> {
+		Ok(Process(command(args, env, dir).spawn()?))
 	}
-	
+
+	/// Kills the child and every process below it
 	pub fn kill(&mut self) {
+		for pid in process_tree(self.0.id(), &process_parents()).into_iter().skip(1) {
+			unsafe {
+				libc::kill(pid as libc::pid_t, libc::SIGKILL);
+			}
+		}
 		self.0.kill().ok();
+		self.0.wait().ok();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{thread, time::{Duration, Instant}};
+	use super::*;
+
+	#[test]
+	fn test_process_tree() {
+		let parents = [(10, 1), (11, 10), (12, 10), (13, 12), (20, 1), (21, 20)];
+		assert_eq!(process_tree(10, &parents), [10, 11, 12, 13]);
+		assert_eq!(process_tree(13, &parents), [13]);
+		let me = std::process::id();
+		assert!(process_parents().iter().any(|(pid, _)| *pid == me));
+	}
+
+	#[cfg(target_os = "linux")]
+	#[test]
+	fn test_kill_pipeline() {
+		let mut process = Process::run(&["sh", "-c", "sleep 300 | sleep 301"], &[], None).unwrap();
+		let root = process.0.id();
+		let deadline = Instant::now() + Duration::from_secs(5);
+		let tree = loop {
+			let tree = process_tree(root, &process_parents());
+			if tree.len() >= 3 || Instant::now() > deadline {
+				break tree
+			}
+			thread::sleep(Duration::from_millis(20));
+		};
+		assert_eq!(tree.len(), 3);
+		process.kill();
+		let alive = |pid: &u32| fs::read_to_string(format!("/proc/{}/stat", pid))
+			.is_ok_and(|stat| !stat.rsplit_once(')').is_some_and(|(_, rest)| rest.trim_start().starts_with('Z')));
+		thread::sleep(Duration::from_millis(100));
+		assert!(!tree.iter().any(alive));
+	}
+
+	/// A child reading the terminal is not stopped, the test runs again as a session leader on a pseudo terminal
+	#[cfg(target_os = "linux")]
+	#[test]
+	fn test_child_reads_terminal() {
+		use std::{io::{Read, Write}, os::{fd::FromRawFd, unix::process::CommandExt}, process::Stdio, sync::mpsc, env, ffi::CStr};
+
+		if env::var_os("REBURN_PTY_CHILD").is_some() {
+			let mut process = Process::run(&["sh", "-c", "read a; echo got:$a"], &[], None).unwrap();
+			process.0.wait().unwrap();
+			return
+		}
+		let (master, slave) = unsafe {
+			let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+			assert!(master >= 0 && libc::grantpt(master) == 0 && libc::unlockpt(master) == 0);
+			let mut name = [0 as libc::c_char; 128];
+			assert_eq!(libc::ptsname_r(master, name.as_mut_ptr(), name.len()), 0);
+			let name = CStr::from_ptr(name.as_ptr()).to_str().unwrap().to_string();
+			(fs::File::from_raw_fd(master), fs::OpenOptions::new().read(true).write(true).open(name).unwrap())
+		};
+		let mut child = unsafe {
+			Command::new(env::current_exe().unwrap())
+				.args(["--exact", "ps::std::tests::test_child_reads_terminal", "--nocapture", "--test-threads=1"])
+				.env("REBURN_PTY_CHILD", "1")
+				.stdin(Stdio::from(slave.try_clone().unwrap()))
+				.stdout(Stdio::from(slave.try_clone().unwrap()))
+				.stderr(Stdio::from(slave))
+				.pre_exec(|| {
+					if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+						return Err(io::Error::last_os_error())
+					}
+					Ok(())
+				})
+				.spawn().unwrap()
+		};
+		let mut reader = master.try_clone().unwrap();
+		let (sender, receiver) = mpsc::channel();
+		thread::spawn(move || {
+			let mut buffer = [0; 1024];
+			while let Ok(n @ 1..) = reader.read(&mut buffer) {
+				sender.send(buffer[..n].to_vec()).ok();
+			}
+		});
+		(&master).write_all(b"typed\n").unwrap();
+		let mut output = Vec::new();
+		while let Ok(chunk) = receiver.recv_timeout(Duration::from_secs(5)) {
+			output.extend(chunk);
+			if String::from_utf8_lossy(&output).contains("got:typed") {
+				break
+			}
+		}
+		child.kill().ok();
+		child.wait().ok();
+		assert!(String::from_utf8_lossy(&output).contains("got:typed"), "{}", String::from_utf8_lossy(&output));
 	}
-}
\ No newline at end of file
+}
@@ -1,4 +1,5 @@
-use std::{process::{Command, Child}, ffi::OsStr, io, collections::LinkedList, mem};
+use std::{process::Child, ffi::OsStr, io, collections::LinkedList, mem, path::Path};
+use super::command;
 use winapi::shared::ntdef::HANDLE;
 use winapi::um::tlhelp32::{CreateToolhelp32Snapshot, Process32First, Process32Next, TH32CS_SNAPPROCESS, PROCESSENTRY32};
 use winapi::um::processthreadsapi::{OpenProcess, TerminateProcess};
@@ -51,8 +52,9 @@ fn process_snapshot() -> LinkedList<(u32, u32)> {
 pub struct Process(Child, HANDLE);
 
 impl Process {
-	pub fn run<S : AsRef<OsStr>>(args: &[S]) -> io::Result<Self> {
-		let child = Command::new(&args[0]).args(&args[1..]).spawn()?;
+	/// `env` is added to the environment of the child, which runs in `dir` if given
+	pub fn run<S : AsRef<OsStr>>(args: &[S], env: &[(String, String)], dir: Option<&Path>) -> io::Result<Self> {
+		let child = command(args, env, dir).spawn()?;
 		let handle = open_process(child.id())?;
 		Ok(Process(child, handle))
 	}
@@ -0,0 +1,34 @@
+use std::{env, fs, ops::Deref, path::{Path, PathBuf}};
+
+/// A directory made for one test, removed with what it holds when dropped, also when an assertion fails
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+  /// `reburn-{name}-{pid}` in the temporary directory, emptied if an earlier run left it behind
+  pub fn new(name: &str) -> Self {
+    let path = env::temp_dir().join(format!("reburn-{}-{}", name, std::process::id()));
+    fs::remove_dir_all(&path).ok();
+    fs::create_dir_all(&path).unwrap();
+    TempDir(path)
+  }
+}
+
+impl Deref for TempDir {
+  type Target = Path;
+
+  fn deref(&self) -> &Path {
+    &self.0
+  }
+}
+
+impl AsRef<Path> for TempDir {
+  fn as_ref(&self) -> &Path {
+    &self.0
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    fs::remove_dir_all(&self.0).ok();
+  }
+}
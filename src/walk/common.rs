@@ -1,3 +1,7 @@
+use std::{fmt, env, path::PathBuf, ffi::OsStr};
+
+use crate::parsing::{selector::MatchOptions, selector_error::SelectorErrorKind};
+
 #[derive(Debug, Clone, PartialEq)]
 pub(super) enum NameMatch {
   Literal(String),
@@ -6,35 +10,50 @@ pub(super) enum NameMatch {
 }
 
 impl NameMatch {
-  pub(super) fn negate(self) -> Result<NameMatch, &'static str> {
+  pub(super) fn negate(self) -> Result<NameMatch, SelectorErrorKind> {
     match self {
       Self::Literal(n) => Ok(Self::NegatedLiteral(n)),
       Self::NegatedLiteral(n) => Ok(Self::Literal(n)),
-      Self::Any => Err("Can not negate any")
+      Self::Any => Err(SelectorErrorKind::InvalidNegation)
     }
   }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub(super) enum RouteItem {
   Name(Vec<NameMatch>),
-  AnySubRoute
+  AnySubRoute,
+  /// The route starts at the filesystem root
+  Root,
+  /// The route starts at the home directory
+  Home,
+  /// Options for the rest of the route
+  Flags(MatchOptions),
+  /// The route only matches a directory, watched recursively
+  Directory
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+  if needle.is_empty() {
+    return Some(0)
+  }
+  haystack.windows(needle.len()).position(|w| w == needle)
 }
 
-fn do_name_match(name: &Vec<NameMatch>, mut remain: &str) -> bool {
+/// Names are compared as bytes so any file name can be matched, even if it is not valid UTF-8
+fn do_name_match(name: &Vec<NameMatch>, mut remain: &[u8]) -> bool {
   use NameMatch::*;
   let mut free_begin = false;
   let mut black_list = Vec::<&String>::new();
-  let black_list_chk = |list: &Vec<_>, free: bool| !list.iter().any(|v| match remain.find(*v) {
-    Some(i) if i == 0 || free => true,
-    _ => false
-  });
+  let black_list_chk = |list: &Vec<&String>, free: bool| !list.iter().any(|v| matches!(
+    find(remain, v.as_bytes()), Some(i) if i == 0 || free
+  ));
   for name in name {
     match name {
       NegatedLiteral(n) => black_list.push(n),
       Any => free_begin = true,
       Literal(n) => {
-        remain = match remain.find(n) {
+        remain = match find(remain, n.as_bytes()) {
           Some(i) if i == 0 || (free_begin && black_list_chk(&black_list, free_begin)) => &remain[(i + n.len())..],
           _ => return false
         };
@@ -43,30 +62,125 @@ fn do_name_match(name: &Vec<NameMatch>, mut remain: &str) -> bool {
       },
     }
   }
-  return remain.is_empty() || free_begin && black_list_chk(&black_list, free_begin)
+  remain.is_empty() || free_begin && black_list_chk(&black_list, free_begin)
+}
+
+/// Dot entries are hidden from wildcards unless the pattern starts with the dot
+pub(super) fn is_visible(name: &[NameMatch], src: &[u8], options: &MatchOptions) -> bool {
+  options.hidden || !src.starts_with(b".") || matches!(name.first(), Some(NameMatch::Literal(l)) if l.starts_with('.'))
+}
+
+/// Lower case of a name, only ASCII letters are changed if the name is not valid UTF-8
+fn fold_case(src: &[u8]) -> Vec<u8> {
+  match std::str::from_utf8(src) {
+    Ok(s) => s.to_lowercase().into_bytes(),
+    Err(_) => src.to_ascii_lowercase()
+  }
+}
+
+/// How a route item moves through the directory tree
+#[derive(Debug, PartialEq)]
+pub(super) enum Step {
+  /// `.` and flags stay in the same directory
+  Stay,
+  /// `..`
+  Parent,
+  /// ** goes through any number of directories
+  Deep,
+  /// Names select an entry of the directory
+  Entry,
+  /// Ends the route if the path is a directory
+  Directory
 }
 
 impl RouteItem {
-  pub(super) fn matches(&self, src: &str) -> bool {
-    if let RouteItem::Name(name) = self {
-      return do_name_match(name, src.clone())
+  pub(super) fn step(&self) -> Step {
+    use NameMatch::*;
+
+    match self {
+      Self::Name(name) if name[..] == [Literal(".".to_string())] => Step::Stay,
+      Self::Name(name) if name[..] == [Literal("..".to_string())] => Step::Parent,
+      Self::Name(_) => Step::Entry,
+      Self::AnySubRoute => Step::Deep,
+      Self::Root | Self::Home | Self::Flags(_) => Step::Stay,
+      Self::Directory => Step::Directory,
+    }
+  }
+
+  pub(super) fn matches<S: AsRef<OsStr>>(&self, src: S, options: &MatchOptions) -> bool {
+    let src = src.as_ref().as_encoded_bytes();
+    match self {
+      RouteItem::Name(name) if !is_visible(name, src, options) => false,
+      RouteItem::Name(name) if options.ignore_case => {
+        let lower = |n: &String| n.to_lowercase();
+        let name: Vec<_> = name.iter().map(|n| match n {
+          NameMatch::Literal(l) => NameMatch::Literal(lower(l)),
+          NameMatch::NegatedLiteral(l) => NameMatch::NegatedLiteral(lower(l)),
+          NameMatch::Any => NameMatch::Any,
+        }).collect();
+        do_name_match(&name, &fold_case(src))
+      },
+      RouteItem::Name(name) => do_name_match(name, src),
+      RouteItem::AnySubRoute => is_visible(&[], src, options),
+      _ => true
     }
-    return true
+  }
+
+  pub(super) fn is_anchor(&self) -> bool {
+    matches!(self, Self::Root | Self::Home)
   }
 
   pub(super) fn omittable(&self) -> bool {
     matches!(self, Self::AnySubRoute)
   }
+}
+
+pub(super) type Route = Vec<RouteItem>;
+
+pub(super) fn home_dir() -> Option<PathBuf> {
+  env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })
+    .filter(|h| !h.is_empty()).map(PathBuf::from)
+}
+
+impl fmt::Display for NameMatch {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Literal(n) => write!(f, "{}", n),
+      Self::NegatedLiteral(n) => write!(f, "!{}", n),
+      Self::Any => write!(f, "*"),
+    }
+  }
+}
 
-  pub(super) fn is_name(&self, matcher: &[NameMatch]) -> bool {
-    if let Self::Name(name) = self {
-      return &name[..] == matcher
+impl fmt::Display for RouteItem {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Name(name) => name.iter().try_for_each(|n| write!(f, "{}", n)),
+      Self::AnySubRoute => write!(f, "**"),
+      Self::Root => Ok(()),
+      Self::Home => write!(f, "~"),
+      Self::Flags(options) => write!(f, "{}", options),
+      Self::Directory => Ok(()),
     }
-    false
   }
 }
 
-pub(super) type Route = Vec<RouteItem>;
+/// Writes a route back as a selector
+pub(super) fn route_to_string(route: &[RouteItem]) -> String {
+  let mut out = String::new();
+  let mut separate = false;
+  for item in route {
+    if separate {
+      out.push('/');
+    }
+    out.push_str(&item.to_string());
+    separate = !matches!(item, RouteItem::Flags(_));
+  }
+  if route.last() == Some(&RouteItem::Root) {
+    out.push('/');
+  }
+  out
+}
 
 #[cfg(test)]
 mod tests {
@@ -76,46 +190,92 @@ mod tests {
 
   #[test]
   fn test_matches() {
-    assert!(AnySubRoute.matches("anything i want"));
+    let d = MatchOptions::default();
+    assert!(AnySubRoute.matches("anything i want", &d));
     let pattern = Name(vec![Literal("sm".to_string()), Any, Literal("t".to_string())]);
-    assert!(pattern.matches("smt"));
-    assert!(pattern.matches("sm t"));
-    assert!(pattern.matches("sm__t"));
-    assert!(!pattern.matches("sm__t_"));
-    assert!(!pattern.matches("_sm__t"));
+    assert!(pattern.matches("smt", &d));
+    assert!(pattern.matches("sm t", &d));
+    assert!(pattern.matches("sm__t", &d));
+    assert!(!pattern.matches("sm__t_", &d));
+    assert!(!pattern.matches("_sm__t", &d));
     let pattern = Name(vec![Any, Literal("smt".to_string()), Any]);
-    assert!(pattern.matches("smt"));
-    assert!(pattern.matches("_smt"));
-    assert!(pattern.matches("smt_"));
-    assert!(!pattern.matches("sm"));
-    assert!(!pattern.matches("mt"));
+    assert!(pattern.matches("smt", &d));
+    assert!(pattern.matches("_smt", &d));
+    assert!(pattern.matches("smt_", &d));
+    assert!(!pattern.matches("sm", &d));
+    assert!(!pattern.matches("mt", &d));
     let pattern = Name(vec![Any, NegatedLiteral("_gen".to_string())]);
-    assert!(pattern.matches("afile"));
-    assert!(pattern.matches("afile_other"));
-    assert!(!pattern.matches("afile_gen"));
-    assert!(!pattern.matches("afile_gen_other"));
+    assert!(pattern.matches("afile", &d));
+    assert!(pattern.matches("afile_other", &d));
+    assert!(!pattern.matches("afile_gen", &d));
+    assert!(!pattern.matches("afile_gen_other", &d));
     let pattern = Name(vec![Any, NegatedLiteral("_gen".to_string()), Literal(".rs".to_string())]);
-    assert!(pattern.matches("afile.rs"));
-    assert!(pattern.matches("afile_other.rs"));
-    assert!(!pattern.matches("afile_gen.rs"));
-    assert!(!pattern.matches("afile"));
-    assert!(!pattern.matches("afile_other"));
+    assert!(pattern.matches("afile.rs", &d));
+    assert!(pattern.matches("afile_other.rs", &d));
+    assert!(!pattern.matches("afile_gen.rs", &d));
+    assert!(!pattern.matches("afile", &d));
+    assert!(!pattern.matches("afile_other", &d));
     let pattern = Name(vec![NegatedLiteral("private_".to_string()), Any]);
-    assert!(pattern.matches("afile.rs"));
-    assert!(pattern.matches("afile_other.rs"));
-    assert!(pattern.matches("anything"));
-    assert!(pattern.matches("priv_smt"));
-    assert!(!pattern.matches("private_anything"));
-    assert!(!pattern.matches("private_"));
+    assert!(pattern.matches("afile.rs", &d));
+    assert!(pattern.matches("afile_other.rs", &d));
+    assert!(pattern.matches("anything", &d));
+    assert!(pattern.matches("priv_smt", &d));
+    assert!(!pattern.matches("private_anything", &d));
+    assert!(!pattern.matches("private_", &d));
     let pattern = Name(vec![NegatedLiteral("private_".to_string()), Literal("file".to_string())]);
-    assert!(pattern.matches("file"));
+    assert!(pattern.matches("file", &d));
     //makes no sense to use a negated without a wildcard
-    assert!(!pattern.matches("private_file"));
-    assert!(!pattern.matches("anything_file"));
+    assert!(!pattern.matches("private_file", &d));
+    assert!(!pattern.matches("anything_file", &d));
     let pattern = Name(vec![Any, NegatedLiteral("avoid".to_string()), Literal("_".to_string()), Any]);
-    assert!(pattern.matches("_avoid"));
-    assert!(pattern.matches("_avoid_smt"));
-    assert!(!pattern.matches("avoid_"));
-    assert!(!pattern.matches("avoid"));
+    assert!(pattern.matches("_avoid", &d));
+    assert!(pattern.matches("_avoid_smt", &d));
+    assert!(!pattern.matches("avoid_", &d));
+    assert!(!pattern.matches("avoid", &d));
+  }
+
+  #[test]
+  fn test_route_to_string() {
+    let name = Name(vec![NegatedLiteral("_".to_string()), Any, Literal(".rs".to_string())]);
+    assert_eq!(route_to_string(&[Root, Name(vec![Literal("etc".to_string())]), AnySubRoute, name]), "/etc/**/!_*.rs");
+    assert_eq!(route_to_string(&[Root]), "/");
+    assert_eq!(route_to_string(&[Home, AnySubRoute]), "~/**");
+    let flags = Flags(MatchOptions { ignore_case: true, hidden: false });
+    assert_eq!(route_to_string(&[flags.clone(), Root, AnySubRoute]), "(?i)/**");
+    assert_eq!(route_to_string(&[flags, Root]), "(?i)/");
+    assert_eq!(route_to_string(&[Name(vec![Any]), Directory]), "*/");
+  }
+
+  #[test]
+  fn test_matches_options() {
+    let d = MatchOptions::default();
+    let i = MatchOptions { ignore_case: true, hidden: false };
+    let h = MatchOptions { ignore_case: false, hidden: true };
+    let pattern = Name(vec![Any, Literal(".rs".to_string())]);
+    assert!(!pattern.matches("main.RS", &d));
+    assert!(pattern.matches("main.RS", &i));
+    assert!(!pattern.matches(".hidden.rs", &d));
+    assert!(pattern.matches(".hidden.rs", &h));
+    let pattern = Name(vec![Literal(".e".to_string()), Any]);
+    assert!(pattern.matches(".env.swp", &d));
+    assert!(pattern.matches(".Env", &i));
+    let pattern = Name(vec![Any, NegatedLiteral("_GEN".to_string())]);
+    assert!(!pattern.matches("a_gen", &i));
+    assert!(!AnySubRoute.matches(".git", &d));
+    assert!(AnySubRoute.matches(".git", &h));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_matches_bytes() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let d = MatchOptions::default();
+    let i = MatchOptions { ignore_case: true, hidden: false };
+    let latin1 = OsStr::from_bytes(b"CAF\xe9_gen.rs");
+    assert!(Name(vec![Any, Literal(".rs".to_string())]).matches(latin1, &d));
+    assert!(Name(vec![Literal("caf".to_string()), Any]).matches(latin1, &i));
+    assert!(!Name(vec![Any, NegatedLiteral("_gen".to_string()), Literal(".rs".to_string())]).matches(latin1, &d));
+    assert!(!Name(vec![Literal("café".to_string()), Any]).matches(latin1, &i));
   }
 }
\ No newline at end of file
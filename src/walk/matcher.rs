@@ -0,0 +1,190 @@
+use std::{path::{Path, PathBuf, Component}, collections::HashSet, ffi::OsString};
+use crate::parsing::selector::{Selector, MatchOptions};
+use super::reduce::*;
+use super::common::*;
+use super::trie::RouteTrie;
+
+/// A selector compiled to test paths without touching the filesystem
+#[derive(Debug)]
+pub struct Matcher {
+  routes: Vec<Route>,
+  bases: Vec<(PathBuf, RouteTrie)>
+}
+
+struct PathMatch<'a> {
+  trie: &'a RouteTrie,
+  names: Vec<OsString>,
+  visited: HashSet<(usize, usize)>,
+  found: Vec<usize>
+}
+
+impl PathMatch<'_> {
+  /// `node` is alive after the first `pos` names of the path
+  fn advance(&mut self, node: usize, pos: usize) {
+    if !self.visited.insert((node, pos)) {
+      return
+    }
+    let trie = self.trie;
+    let options = trie.options(node);
+    if pos == self.names.len() {
+      self.found.extend(trie.ends(node));
+    }
+    for (item, next) in trie.edges(node) {
+      match item.step() {
+        Step::Stay => self.advance(*next, pos),
+        Step::Parent => {
+          if pos > 0 {
+            self.advance(*next, pos - 1);
+          }
+          if self.names.get(pos).is_some_and(|n| n == "..") {
+            self.advance(*next, pos + 1);
+          }
+        },
+        Step::Deep => {
+          // a trailing ** watches everything below, except what is hidden from it
+          if self.names[pos..].iter().all(|n| RouteItem::AnySubRoute.matches(n, options)) {
+            self.found.extend(trie.ends(*next));
+          }
+          self.advance(*next, pos);
+        },
+        // so does a trailing slash, the path is taken as a directory
+        Step::Directory => self.found.extend(trie.ends(*next)),
+        Step::Entry => if pos < self.names.len() && item.matches(&self.names[pos], options) {
+          self.advance(*next, pos + 1);
+        }
+      }
+    }
+    if trie.is_deep(node) && pos < self.names.len() && RouteItem::AnySubRoute.matches(&self.names[pos], options) {
+      self.advance(node, pos + 1);
+    }
+  }
+}
+
+fn names_of(path: &Path) -> Vec<OsString> {
+  path.components().filter_map(|c| match c {
+    Component::Normal(name) => Some(name.to_os_string()),
+    Component::ParentDir => Some(OsString::from("..")),
+    _ => None
+  }).collect()
+}
+
+/// `path` written from `dir`, with `..` to leave it when needed
+fn relative_path(path: &Path, dir: &Path) -> PathBuf {
+  let (path, dir): (Vec<_>, Vec<_>) = (path.components().collect(), dir.components().collect());
+  let common = path.iter().zip(&dir).take_while(|(a, b)| a == b).count();
+  dir[common..].iter().map(|_| Component::ParentDir).chain(path[common..].iter().copied()).collect()
+}
+
+impl Matcher {
+  pub fn new(selector: &Selector, options: MatchOptions) -> Result<Self, &'static str> {
+    let routes = recursive_join(selector).map_err(|(kind, _)| kind.message())?;
+    let bases = RouteTrie::compile_bases(&routes, options)?;
+    Ok(Matcher { routes, bases })
+  }
+
+  fn route_id(&self, path: &Path) -> Option<usize> {
+    self.bases.iter().filter_map(|(base, trie)| {
+      let rest = if base == Path::new(".") {
+        Some(path).filter(|p| p.is_relative())
+      } else {
+        path.strip_prefix(base).ok()
+      }?;
+      let mut state = PathMatch { trie, names: names_of(rest), visited: HashSet::new(), found: Vec::new() };
+      state.advance(RouteTrie::ROOT, 0);
+      state.found.into_iter().min()
+    }).min()
+  }
+
+  /// The first route matching the path, written back as a selector
+  pub fn matching_route(&self, path: &Path) -> Option<String> {
+    self.route_id(path).map(|id| route_to_string(&self.routes[id]))
+  }
+
+  pub fn is_match(&self, path: &Path) -> bool {
+    self.route_id(path).is_some()
+  }
+
+  /// Whether an absolute path matches when relative routes start from `dir`
+  pub fn is_match_from(&self, path: &Path, dir: &Path) -> bool {
+    self.is_match(path) || path.is_absolute() && self.is_match(&relative_path(path, dir))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::parsing::selector::parse_selector;
+  use super::*;
+
+  fn matcher(pattern: &str) -> Matcher {
+    Matcher::new(&parse_selector(pattern).unwrap(), MatchOptions::default()).unwrap()
+  }
+
+  #[test]
+  fn test_is_match() {
+    let m = matcher("src/**/*!_gen.rs,Cargo.toml,/etc/app/*.conf,docs/**");
+    assert!(m.is_match(Path::new("src/a/b.rs")));
+    assert!(m.is_match(Path::new("src/b.rs")));
+    assert!(m.is_match(Path::new("./src/a/b/c.rs")));
+    assert!(!m.is_match(Path::new("src/a/b_gen.rs")));
+    assert!(!m.is_match(Path::new("src")));
+    assert!(m.is_match(Path::new("Cargo.toml")));
+    assert!(!m.is_match(Path::new("other/Cargo.toml")));
+    assert!(m.is_match(Path::new("/etc/app/x.conf")));
+    assert!(!m.is_match(Path::new("etc/app/x.conf")));
+    assert!(m.is_match(Path::new("docs")));
+    assert!(m.is_match(Path::new("docs/a/b/c")));
+    assert!(matcher("a/./c").is_match(Path::new("a/c")));
+    assert!(matcher("../x/*").is_match(Path::new("../x/y")));
+  }
+
+  #[test]
+  fn test_options() {
+    let m = matcher("src/**/*.rs,(?i)README.md,(?h)conf/*");
+    assert!(!m.is_match(Path::new("src/.cache/a.rs")));
+    assert!(!m.is_match(Path::new("src/.a.rs")));
+    let deep = matcher("**,src/**");
+    assert!(!deep.is_match(Path::new(".git/HEAD")));
+    assert!(!deep.is_match(Path::new("src/.cache/x")));
+    assert!(deep.is_match(Path::new("src/a/b")));
+    assert!(matcher("(?h)src/**").is_match(Path::new("src/.cache/x")));
+    assert!(matcher(".git/**").is_match(Path::new(".git/HEAD")));
+    assert!(m.is_match(Path::new("readme.MD")));
+    assert!(!m.is_match(Path::new("SRC/a.rs")));
+    assert!(m.is_match(Path::new("conf/.env")));
+    let m = Matcher::new(&parse_selector("src/**/*.rs,x/.*").unwrap(), MatchOptions { ignore_case: true, hidden: true }).unwrap();
+    assert!(m.is_match(Path::new("Src/.cache/a.RS")));
+    assert!(m.is_match(Path::new("x/.env")));
+  }
+
+  #[test]
+  fn test_is_match_from() {
+    let m = matcher("src/**,../lib/*.rs,/etc/app/*.conf");
+    let dir = Path::new("/home/me/project");
+    assert!(m.is_match_from(Path::new("/home/me/project/src/a.rs"), dir));
+    assert!(m.is_match_from(Path::new("/home/me/lib/a.rs"), dir));
+    assert!(m.is_match_from(Path::new("/etc/app/x.conf"), dir));
+    assert!(!m.is_match_from(Path::new("/home/me/project/src/.cache/x"), dir));
+    assert!(!m.is_match_from(Path::new("/home/me/src/a.rs"), dir));
+    assert_eq!(relative_path(Path::new("/a/b/c"), Path::new("/a/d/e")), Path::new("../../b/c"));
+  }
+
+  #[test]
+  fn test_depth_and_directories() {
+    let m = matcher("a/**{1,2}/*.rs,b/*/");
+    assert!(!m.is_match(Path::new("a/x.rs")));
+    assert!(m.is_match(Path::new("a/1/x.rs")));
+    assert!(m.is_match(Path::new("a/1/2/x.rs")));
+    assert!(!m.is_match(Path::new("a/1/2/3/x.rs")));
+    assert!(m.is_match(Path::new("b/c")));
+    assert!(m.is_match(Path::new("b/c/d/e")));
+    assert!(!m.is_match(Path::new("b")));
+  }
+
+  #[test]
+  fn test_matching_route() {
+    let m = matcher("{a,b}/*.rs,**/*.rs");
+    assert_eq!(m.matching_route(Path::new("b/x.rs")), Some("b/*.rs".to_string()));
+    assert_eq!(m.matching_route(Path::new("c/x.rs")), Some("**/*.rs".to_string()));
+    assert_eq!(m.matching_route(Path::new("c/x.toml")), None);
+  }
+}
@@ -1,5 +1,9 @@
 mod common;
 mod reduce;
+mod trie;
+mod matcher;
+#[allow(clippy::module_inception)]
 mod walk;
 
-pub use walk::matches;
\ No newline at end of file
+pub use walk::{matches, expand, check};
+pub use matcher::Matcher;
\ No newline at end of file
@@ -1,72 +1,96 @@
-use crate::parsing::selector::Selector;
+use crate::parsing::{selector::{Selector, Span}, selector_error::SelectorErrorKind};
 use super::common::*;
 
-fn reduce_routes<F: Fn(Route, Route) -> Result<Route, &'static str>>(routes: Vec<Vec<Route>>, combine: F)
--> Result<Vec<Route>, &'static str> {
-  routes.into_iter().map(|i| Ok(i)).reduce(|a, b| match (a, b) {
+/// What can not be expanded, with where it is written
+pub(super) type ReduceError = (SelectorErrorKind, Span);
+
+fn reduce_routes<F: Fn(Route, Route) -> Result<Route, SelectorErrorKind>>(routes: Vec<Vec<Route>>, combine: F)
+-> Result<Vec<Route>, SelectorErrorKind> {
+  routes.into_iter().map(Ok).reduce(|a, b| match (a, b) {
     (a @ Err(_), _) | (_, a @ Err(_)) => a,
-    (Ok(ref a), Ok(ref b)) => a.into_iter()
-      .map( |a| b.into_iter().map(|b| combine(a.clone(), b.clone())) ).flatten().collect()
+    (Ok(ref a), Ok(ref b)) => a.iter()
+      .flat_map( |a| b.iter().map(|b| combine(a.clone(), b.clone())) ).collect()
   }).unwrap()
 }
 
-fn route_combine(a: &[RouteItem], b: &[RouteItem]) -> Result<Route, &'static str> {
+fn route_combine(a: &[RouteItem], b: &[RouteItem]) -> Result<Route, SelectorErrorKind> {
   use RouteItem::*;
 
   match (a, b) {
-    ([.., AnySubRoute], _) | (_, [AnySubRoute, ..]) => Err("Trying to concat with **"),
-    (_, []) | ([], _) => Err("Empty concat"),
+    ([.., AnySubRoute | Root | Home | Flags(_) | Directory], _) | (_, [AnySubRoute | Root | Home | Flags(_) | Directory, ..])
+    | (_, []) | ([], _) => Err(SelectorErrorKind::InvalidJoin),
     ([init @ .., Name(left)], [Name(right), tail @ ..]) => Ok({
       let mut v = Vec::new();
-      v.extend(init.iter().map(|x| x.clone()));
+      v.extend(init.iter().cloned());
       v.push(Name(
         vec![left.clone(), right.clone()].into_iter().flatten().collect::<Vec<_>>()
       ));
-      v.extend(tail.iter().map(|x| x.clone()));
+      v.extend(tail.iter().cloned());
       v
     })
   }
 }
 
 #[inline]
-fn recursive_join_many(v: &Vec<Selector>) -> Result<Vec<Vec<Route>>, &'static str> {
-  v.into_iter().map(recursive_join).collect::<Result<Vec<_>, _>>()
+fn recursive_join_many(v: &[Selector]) -> Result<Vec<Vec<Route>>, ReduceError> {
+  v.iter().map(recursive_join).collect::<Result<Vec<_>, _>>()
 }
 
 #[inline]
-fn negate(v: Vec<RouteItem>) -> Result<Route, &'static str> {
+fn negate(v: Vec<RouteItem>) -> Result<Route, SelectorErrorKind> {
   use RouteItem::*;
 
-  if v.len() == 1 {
-    match &v[0] {
-      RouteItem::Name(n) if n.len() == 1 => return Ok(vec![Name(vec![n[0].clone().negate()?])]),
-      _ => ()
-    }
+  match &v[..] {
+    [Name(n)] if n.len() == 1 => Ok(vec![Name(vec![n[0].clone().negate()?])]),
+    _ => Err(SelectorErrorKind::InvalidNegation)
   }
-  return Err("Only literals can be negated")
 }
 
-pub(super) fn recursive_join(selector: &Selector) -> Result<Vec<Route>, &'static str> {
+pub(super) fn recursive_join(selector: &Selector) -> Result<Vec<Route>, ReduceError> {
   use Selector::*;
   use RouteItem::*;
   use NameMatch::*;
 
   Ok(match selector {
-    Option(v) | Route(v) | Concat(v) if v.is_empty() => return Err("Empty group"),
+    Option(v) if v.is_empty() => return Err((SelectorErrorKind::Empty, 0..0)),
+    Route(v, at) | Concat(v, at) if v.is_empty() => return Err((SelectorErrorKind::Empty, at.clone())),
     Option(v) => recursive_join_many(v)?.into_iter().flatten().collect(),
-    Route(v) => reduce_routes(recursive_join_many(v)?, |a, b| {
+    Route(v, at) => reduce_routes(recursive_join_many(v)?, |a, b| {
+      if b.iter().any(RouteItem::is_anchor) {
+        return Err(SelectorErrorKind::MisplacedAnchor)
+      }
+      if a.last() == Some(&RouteItem::Directory) && !b.is_empty() {
+        return Err(SelectorErrorKind::MisplacedDirectory)
+      }
       let mut v = Vec::new();
-      v.extend(a.iter().map(|x| x.clone()));
-      v.extend(b.iter().map(|x| x.clone()));
+      v.extend(a.iter().cloned());
+      v.extend(b.iter().cloned());
       Ok(v)
-    })?,
-    Concat(v) => reduce_routes(recursive_join_many(v)?,
+    }).map_err(|kind| (kind, at.clone()))?,
+    Concat(v, at) => reduce_routes(recursive_join_many(v)?,
       |a, b| route_combine(&a[..], &b[..])
-    )?,
-    Not(v) => recursive_join(v)?.into_iter()
-      .map(negate).collect::<Result<Vec<_>, _>>()?,
+    ).map_err(|kind| (kind, at.clone()))?,
+    // the alias is written elsewhere, its name is what the pattern shows
+    Alias(_, v, at) => recursive_join(v).map_err(|_| (SelectorErrorKind::InvalidAlias, at.clone()))?,
+    Not(v, at) => recursive_join(v)?.into_iter()
+      .map(negate).collect::<Result<Vec<_>, _>>().map_err(|kind| (kind, at.clone()))?,
     WildCard => vec![vec![Name(vec![Any])]],
     WildCardDepth => vec![vec![AnySubRoute]],
+    // one route for each depth, they share the trie anyway
+    WildCardDepthBounded(min, Some(max)) => (*min..=*max).map(|depth| vec![Name(vec![Any]); depth]).collect(),
+    WildCardDepthBounded(min, None) => vec![{
+      let mut v = vec![Name(vec![Any]); *min];
+      v.push(AnySubRoute);
+      v
+    }],
+    Selector::Directory => vec![vec![RouteItem::Directory]],
+    Selector::Root => vec![vec![RouteItem::Root]],
+    Selector::Home => vec![vec![RouteItem::Home]],
+    Selector::Flags(options, v) => recursive_join(v)?.into_iter().map(|route| {
+      let mut v = vec![RouteItem::Flags(*options)];
+      v.extend(route);
+      v
+    }).collect(),
     Word(n) => vec![vec![Name(vec![Literal(n.clone())])]],
   })
 }
\ No newline at end of file
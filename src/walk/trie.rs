@@ -0,0 +1,150 @@
+use std::path::PathBuf;
+
+use crate::parsing::selector::MatchOptions;
+use super::common::*;
+
+#[derive(Debug, Default)]
+struct Node {
+  edges: Vec<(RouteItem, usize)>,
+  /// Index of the routes ending at this node
+  ends: Vec<usize>,
+  /// The node is the target of a ** edge, so it stays alive in every subdirectory
+  deep: bool,
+  /// Options used to match the edges of the node
+  options: MatchOptions
+}
+
+/// Prefix tree of routes, routes sharing a prefix share the nodes of the prefix
+#[derive(Debug)]
+pub(super) struct RouteTrie(Vec<Node>);
+
+/// Splits the anchor of a route from the rest, relative routes start at `.`
+fn split_base(route: &[RouteItem]) -> Result<(PathBuf, Route), &'static str> {
+  // flags may come before the anchor
+  let flags = route.iter().take_while(|i| matches!(i, RouteItem::Flags(_))).count();
+  let (flags, route) = route.split_at(flags);
+  let (base, rest) = match route {
+    [RouteItem::Root, rest @ ..] => (PathBuf::from("/"), rest),
+    [RouteItem::Home, rest @ ..] => (home_dir().ok_or("Home directory not found")?, rest),
+    rest => (PathBuf::from("."), rest)
+  };
+  Ok((base, flags.iter().chain(rest).cloned().collect()))
+}
+
+impl RouteTrie {
+  pub(super) const ROOT: usize = 0;
+
+  fn new(options: MatchOptions) -> Self {
+    RouteTrie(vec![Node { options, ..Node::default() }])
+  }
+
+  #[cfg(test)]
+  pub(super) fn compile<I: IntoIterator<Item = Route>>(routes: I) -> Self {
+    let mut trie = Self::new(MatchOptions::default());
+    routes.into_iter().enumerate().for_each(|(id, route)| trie.insert(&route, id));
+    trie
+  }
+
+  /// One trie for each place the routes start from, routes keep their index in `routes`
+  pub(super) fn compile_bases(routes: &[Route], options: MatchOptions)
+  -> Result<Vec<(PathBuf, RouteTrie)>, &'static str> {
+    let mut bases = Vec::<(PathBuf, RouteTrie)>::new();
+    for (id, route) in routes.iter().enumerate() {
+      let (base, route) = split_base(route)?;
+      match bases.iter_mut().find(|(b, _)| *b == base) {
+        Some((_, trie)) => trie.insert(&route, id),
+        None => {
+          let mut trie = Self::new(options);
+          trie.insert(&route, id);
+          bases.push((base, trie));
+        }
+      }
+    }
+    Ok(bases)
+  }
+
+  fn insert(&mut self, route: &[RouteItem], id: usize) {
+    let mut current = Self::ROOT;
+    for item in route {
+      current = match self.0[current].edges.iter().find(|(i, _)| i == item) {
+        Some((_, next)) => *next,
+        None => {
+          let next = self.0.len();
+          let options = match item {
+            RouteItem::Flags(flags) => self.0[current].options.union(*flags),
+            _ => self.0[current].options
+          };
+          self.0.push(Node { deep: item.omittable(), options, ..Node::default() });
+          self.0[current].edges.push((item.clone(), next));
+          next
+        }
+      }
+    }
+    self.0[current].ends.push(id);
+  }
+
+  pub(super) fn edges(&self, node: usize) -> &[(RouteItem, usize)] {
+    &self.0[node].edges
+  }
+
+  pub(super) fn is_terminal(&self, node: usize) -> bool {
+    !self.0[node].ends.is_empty()
+  }
+
+  pub(super) fn ends(&self, node: usize) -> &[usize] {
+    &self.0[node].ends
+  }
+
+  pub(super) fn is_deep(&self, node: usize) -> bool {
+    self.0[node].deep
+  }
+
+  pub(super) fn options(&self, node: usize) -> &MatchOptions {
+    &self.0[node].options
+  }
+
+  #[cfg(test)]
+  fn len(&self) -> usize {
+    self.0.len()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use NameMatch::*;
+  use RouteItem::*;
+
+  fn lit(s: &str) -> RouteItem {
+    Name(vec![Literal(s.to_string())])
+  }
+
+  #[test]
+  fn test_compile_bases() {
+    let anchored = |v: Vec<RouteItem>| { let mut r = vec![Root]; r.extend(v); r };
+    let routes = vec![
+      anchored(vec![lit("etc"), lit("a")]), vec![lit("src")], vec![Home, lit(".config")], anchored(vec![lit("etc")])
+    ];
+    let bases = RouteTrie::compile_bases(&routes, MatchOptions::default()).unwrap();
+    let bases: Vec<_> = bases.iter().map(|(b, t)| (b.clone(), t.len())).collect();
+    assert_eq!(bases, vec![(PathBuf::from("/"), 3), (PathBuf::from("."), 2), (home_dir().unwrap(), 2)]);
+  }
+
+  #[test]
+  fn test_shared_prefix() {
+    let trie = RouteTrie::compile(vec![
+      vec![lit("a"), lit("b")],
+      vec![lit("a"), lit("c")],
+      vec![lit("a"), AnySubRoute, lit("b")],
+      vec![lit("a")],
+    ]);
+    // root, a, b, c, **, b
+    assert_eq!(trie.len(), 6);
+    let a = trie.edges(RouteTrie::ROOT)[0].1;
+    assert_eq!(trie.ends(a), &[3]);
+    assert_eq!(trie.edges(a).len(), 3);
+    let deep = trie.edges(a)[2].1;
+    assert!(trie.is_deep(deep));
+    assert!(!trie.is_terminal(deep));
+  }
+}
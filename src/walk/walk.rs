@@ -1,60 +1,243 @@
 use std::{path::{PathBuf, Path}, thread, sync::{mpsc::{channel, Sender}, Arc}, fs::{self, DirEntry}};
 use notify::RecursiveMode::{self, *};
-use crate::parsing::selector::Selector;
+use crate::parsing::selector::{Selector, MatchOptions};
 use super::reduce::*;
 use super::common::*;
+use super::trie::RouteTrie;
+use super::matcher::Matcher;
 
 fn files_in<P: AsRef<Path>>(path: &P) -> impl Iterator<Item = DirEntry> {
   fs::read_dir(path)
-  .and_then(|d| Ok(d.collect::<Vec<_>>()))
+  .map(|d| d.collect::<Vec<_>>())
   .unwrap_or_default()
-  .into_iter().filter_map(move |f| f.and_then(|e| Ok(e)).ok())
+  .into_iter().filter_map(move |f| f.ok())
 }
 
-struct Walker(Sender<(PathBuf, RecursiveMode)>);
+#[inline]
+fn push_unique(live: &mut Vec<usize>, node: usize) {
+  if !live.contains(&node) {
+    live.push(node)
+  }
+}
+
+/// Paths the walk never reads nor watches, relative routes start from the path
+struct Ignore(Option<Arc<Matcher>>, PathBuf);
+
+impl Ignore {
+  fn skips(&self, path: &Path) -> bool {
+    self.0.as_ref().is_some_and(|m| m.is_match_from(path, &self.1))
+  }
+}
+
+#[derive(Clone)]
+struct Walker(Sender<(PathBuf, RecursiveMode)>, Arc<RouteTrie>, Arc<Ignore>);
 
 impl Walker {
   fn send(&self, path: PathBuf, mode: RecursiveMode) {
     self.0.send((path, mode)).ok();
   }
 
-  fn async_match_route(&self, remain: Arc<[RouteItem]>, path: PathBuf) {
-    let sender = self.0.clone();
-    thread::spawn(move || Walker(sender).match_route(&remain, path));
+  fn async_visit(&self, path: PathBuf, live: Vec<usize>) {
+    let walker = self.clone();
+    thread::spawn(move || walker.visit(path, live));
   }
 
-  fn match_route(&self, remain: &[RouteItem], path: PathBuf) {
-    use NameMatch::*;
-
-    match remain {
-      [RouteItem::AnySubRoute] => self.send(path, Recursive),
-      [n, last @ ..] if n.is_name(&[Literal(".".to_string())]) =>
-        self.match_route(last.into(), path),
-      [n, last @ ..] if n.is_name(&[Literal("..".to_string())]) => if let Some(n) = path.parent() {
-        self.match_route(last.into(), n.to_path_buf());
-      }
-      [n, last @ ..] => {
-        let files: Vec<_> = files_in(&path)
-          .filter(|p| n.matches(p.file_name().to_str().unwrap())).collect();
-        files.iter().for_each(|f| self.async_match_route(last.into(), f.path()));
-        if n.omittable() {
-          files.iter().for_each(|f| self.async_match_route(remain.into(), f.path()));
-          self.match_route(last.into(), path);
+  /// Every node in `live` is a trie branch still able to match under `path`,
+  /// the directory is read once and its entries tested against all of them
+  fn visit(&self, path: PathBuf, mut live: Vec<usize>) {
+    if self.2.skips(&path) {
+      return
+    }
+    let trie = &self.1;
+    let (mut terminal, mut recursive) = (false, false);
+    // edges that do not consume an entry: `.`, `..`, flags and a ** matching nothing
+    let mut i = 0;
+    while i < live.len() {
+      let node = live[i];
+      i += 1;
+      terminal |= trie.is_terminal(node);
+      for (item, next) in trie.edges(node) {
+        match item.step() {
+          Step::Stay => push_unique(&mut live, *next),
+          Step::Parent => if let Some(parent) = path.parent() {
+            self.async_visit(parent.to_path_buf(), vec![*next]);
+          },
+          Step::Deep => {
+            recursive |= trie.is_terminal(*next);
+            push_unique(&mut live, *next);
+          },
+          Step::Directory => recursive |= trie.is_terminal(*next) && path.is_dir(),
+          Step::Entry => ()
         }
       }
-      [] => self.send(path, NonRecursive)
+    }
+    if recursive {
+      // everything below is already covered
+      return self.send(path, Recursive)
+    }
+    if terminal {
+      self.send(path.clone(), NonRecursive);
+    }
+    let steps: Vec<_> = live.iter()
+      .flat_map(|node| trie.edges(*node).iter().map(|(item, next)| (item, *next, trie.options(*node))))
+      .filter(|(item, _, _)| item.step() == Step::Entry)
+      .collect();
+    let deep: Vec<_> = live.iter().copied().filter(|node| trie.is_deep(*node)).collect();
+    if steps.is_empty() && deep.is_empty() {
+      return
+    }
+    for entry in files_in(&path) {
+      let name = entry.file_name();
+      let mut next: Vec<_> = deep.iter().copied()
+        .filter(|node| RouteItem::AnySubRoute.matches(&name, trie.options(*node))).collect();
+      steps.iter().filter(|(item, _, options)| item.matches(&name, options))
+        .for_each(|(_, n, _)| push_unique(&mut next, *n));
+      if !next.is_empty() {
+        self.async_visit(entry.path(), next);
+      }
     }
   }
+}
 
-  fn match_all(&self, routes: impl Iterator<Item = Route>) {
-    routes.for_each(|route| self.match_route(&route[..], PathBuf::from(".")));
-  }
+fn walk_trie(trie: RouteTrie, base: PathBuf, sender: Sender<(PathBuf, RecursiveMode)>, ignore: Arc<Ignore>) {
+  Walker(sender, Arc::new(trie), ignore).visit(base, vec![RouteTrie::ROOT]);
+}
+
+/// The routes a selector expands to, written back as selectors
+pub fn expand(selector: &Selector) -> Result<Vec<String>, &'static str> {
+  Ok(recursive_join(selector).map_err(|(kind, _)| kind.message())?.iter().map(|r| route_to_string(r)).collect())
 }
 
-pub fn matches(selector: Selector) -> Result<impl Iterator<Item = (PathBuf, RecursiveMode)>, &'static str> {
+/// Fails with where the selector is written when it can not be expanded to routes
+pub fn check(selector: &Selector) -> Result<(), ReduceError> {
+  recursive_join(selector).map(|_| ())
+}
+
+/// Relative routes, of the selector and of `ignore`, start from `relative_to`.
+/// What `ignore` matches is neither read nor watched
+pub fn matches(selector: Selector, options: MatchOptions, relative_to: &Path, ignore: Option<Arc<Matcher>>)
+-> Result<impl Iterator<Item = (PathBuf, RecursiveMode)>, &'static str> {
+  let bases = RouteTrie::compile_bases(&recursive_join(&selector).map_err(|(kind, _)| kind.message())?, options)?;
+  let ignore = Arc::new(Ignore(ignore, relative_to.to_path_buf()));
   // channels will be useful for future parallelization
   let (sender, receiver) = channel();
-  // sender will be closed once dropped
-  Walker(sender).match_all(recursive_join(&selector)?.into_iter());
+  for (base, trie) in bases {
+    let base = if base == Path::new(".") { relative_to.to_path_buf() } else { base };
+    walk_trie(trie, base, sender.clone(), ignore.clone());
+  }
+  // sender will be closed once every walker is dropped
   Ok(receiver.into_iter())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{collections::HashSet, time::Instant, sync::mpsc::Receiver};
+  use crate::{parsing::selector::parse_selector, testing::TempDir};
+  use super::*;
+
+  fn tree(root: &Path, depth: usize) {
+    for dir in ["a", "b", "c", "d", "e", "f", "g", "h", "i"] {
+      let path = root.join(dir);
+      fs::create_dir_all(&path).unwrap();
+      for file in ["x.rs", "y.toml", "z.txt"] {
+        fs::write(path.join(file), "").unwrap();
+      }
+      if depth > 0 {
+        tree(&path, depth - 1);
+      }
+    }
+  }
+
+  fn walk(trie: RouteTrie, base: PathBuf) -> Receiver<(PathBuf, RecursiveMode)> {
+    let (sender, receiver) = channel();
+    walk_trie(trie, base.clone(), sender, Arc::new(Ignore(None, base)));
+    receiver
+  }
+
+  fn collect(receiver: Receiver<(PathBuf, RecursiveMode)>) -> HashSet<(PathBuf, bool)> {
+    receiver.into_iter().map(|(p, m)| (p, m == Recursive)).collect()
+  }
+
+  #[test]
+  fn test_walk_trie() {
+    let root = TempDir::new("walk");
+    tree(&root, 1);
+    let routes = recursive_join(&parse_selector(
+      "{a,b}/{c,d}/*.rs,a/**/y.toml,./b/../c/*.txt,e/**,f/*/,g/**{,1}/z.txt,{h,x.rs}/"
+    ).unwrap()).unwrap();
+    let found = collect(walk(RouteTrie::compile(routes), root.to_path_buf()));
+    let mut expected: HashSet<_> = [
+      ("a/c/x.rs", false), ("a/d/x.rs", false), ("b/c/x.rs", false), ("b/d/x.rs", false),
+      ("a/y.toml", false), ("c/z.txt", false), ("e", true), ("g/z.txt", false), ("h", true)
+    ].into_iter().map(|(p, m)| (root.join(p), m)).collect();
+    for dir in ["a", "b", "c", "d", "e", "f", "g", "h", "i"] {
+      expected.insert((root.join("a").join(dir).join("y.toml"), false));
+      expected.insert((root.join("f").join(dir), true));
+      expected.insert((root.join("g").join(dir).join("z.txt"), false));
+    }
+    assert_eq!(found, expected);
+  }
+
+  #[test]
+  fn test_walk_ignore() {
+    let root = TempDir::new("ignore");
+    tree(&root, 1);
+    let ignore = Matcher::new(&parse_selector("{a,b/*}/**,*/z.txt").unwrap(), MatchOptions::default()).unwrap();
+    let found: HashSet<_> = matches(parse_selector("**/*.txt,c/").unwrap(), MatchOptions::default(), &root, Some(Arc::new(ignore)))
+      .unwrap().collect();
+    // `c` is watched whole, `a`, the directories in `b` and the `z.txt` of the first level are never read
+    let mut expected = HashSet::from([(root.join("c"), Recursive)]);
+    for dir in ["d", "e", "f", "g", "h", "i"] {
+      expected.extend(["a", "b", "c", "d", "e", "f", "g", "h", "i"].map(|sub| (root.join(dir).join(sub).join("z.txt"), NonRecursive)));
+    }
+    assert_eq!(found, expected);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_walk_non_utf8() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    let root = TempDir::new("bytes");
+    fs::create_dir_all(root.join(OsStr::from_bytes(b"d\xff"))).unwrap();
+    fs::write(root.join(OsStr::from_bytes(b"d\xff/caf\xe9.rs")), "").unwrap();
+    fs::write(root.join("d\u{ff}.rs"), "").unwrap();
+    let routes = recursive_join(&parse_selector("**/*.rs").unwrap()).unwrap();
+    let found = collect(walk(RouteTrie::compile(routes), root.to_path_buf()));
+    assert_eq!(found, [
+      (root.join(OsStr::from_bytes(b"d\xff/caf\xe9.rs")), false), (root.join("d\u{ff}.rs"), false)
+    ].into_iter().collect());
+  }
+
+  /// Compares the shared trie with one walk per route, run with `cargo test -- --ignored --nocapture`
+  #[test]
+  #[ignore]
+  fn bench_trie_against_per_route() {
+    let root = TempDir::new("bench");
+    tree(&root, 3);
+    let selector = parse_selector("{a,b,c}/{d,e,f}/{g,h,i}/**/*.{rs,toml}").unwrap();
+
+    let start = Instant::now();
+    let routes = recursive_join(&selector).unwrap();
+    let per_route: Vec<_> = routes.iter().map(|r| RouteTrie::compile(vec![r.clone()])).collect();
+    let per_route_compile = start.elapsed();
+    let start = Instant::now();
+    let mut per_route_found = HashSet::new();
+    for trie in per_route {
+      per_route_found.extend(collect(walk(trie, root.to_path_buf())));
+    }
+    let per_route_walk = start.elapsed();
+
+    let start = Instant::now();
+    let trie = RouteTrie::compile(recursive_join(&selector).unwrap());
+    let trie_compile = start.elapsed();
+    let start = Instant::now();
+    let trie_found = collect(walk(trie, root.to_path_buf()));
+    let trie_walk = start.elapsed();
+
+    println!("{} routes", routes.len());
+    println!("per route: compile {:?}, walk {:?}", per_route_compile, per_route_walk);
+    println!("trie:      compile {:?}, walk {:?}", trie_compile, trie_walk);
+    assert_eq!(per_route_found, trie_found);
+  }
+}
@@ -1,4 +1,4 @@
-use std::{sync::mpsc::{channel, Receiver}, path::{Path, PathBuf}};
+use std::{sync::mpsc::{channel, Receiver, RecvTimeoutError}, path::{self, Path, PathBuf}, time::Instant};
 
 use notify::{Watcher, recommended_watcher, RecommendedWatcher, RecursiveMode, Result};
 
@@ -13,7 +13,9 @@ impl WatchingChannel<Vec<PathBuf>> {
 		let (sender, receiver) = channel::<Vec<PathBuf>>();
 		let mut watcher = recommended_watcher(move |res: Result<notify::Event>| match res {
 			Ok(event) => {
-				sender.send(event.paths).ok();
+				// relative and absolute targets report events the same way
+				let paths = event.paths.into_iter().map(|p| path::absolute(&p).unwrap_or(p)).collect();
+				sender.send(paths).ok();
 			},
 			Err(e) => panic!("Watch error: {:?}", e),
 		})?;
@@ -24,6 +26,16 @@ impl WatchingChannel<Vec<PathBuf>> {
 	}
 }
 
+impl<T> WatchingChannel<T> {
+	/// The next change, waiting for it until `deadline` if given
+	pub fn recv_until(&self, deadline: Option<Instant>) -> std::result::Result<T, RecvTimeoutError> {
+		match deadline {
+			Some(deadline) => self.receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+			None => self.receiver.recv().map_err(|_| RecvTimeoutError::Disconnected)
+		}
+	}
+}
+
 impl<T> Iterator for WatchingChannel<T> {
 	type Item = T;
 